@@ -1,19 +1,21 @@
-#![feature(once_cell)]
-#![feature(entry_insert)]
-
 use log::{debug, info};
-use num::{client::Client, idler::Idler};
+use num::{client::Client, idler::Idler, Notification};
 use tokio::net::{TcpListener, TcpStream};
 
 async fn handle_new_connection(tcp_stream: TcpStream) {
     if let Ok(socket) = tokio_tungstenite::accept_async(tcp_stream).await {
-        let client = Client::new(socket);
+        let mut client = Client::new(socket);
+
+        if let Some(token) = client.token().map(str::to_owned) {
+            let _ = client.notify(Notification::Session { token: &token }).await;
+        }
+
         Idler::spawn(client);
         debug!("Connection upgraded to websocket");
     }
 }
 
-const ADDRESS: &'static str = "0.0.0.0:7878";
+const ADDRESS: &str = "0.0.0.0:7878";
 
 #[tokio::main]
 async fn main() {
@@ -0,0 +1,285 @@
+use crate::{
+    scoring::Feedback,
+    secret::{Format, SecretInput},
+    solver::{count_secrets, Solver, Strategy},
+    Directive, Notification, Secret, Settings,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use tokio::{
+    task::{spawn_blocking, JoinHandle},
+    time::{sleep_until, Duration, Instant},
+};
+
+/// The time a bot waits before sending a guess, so that it doesn't play
+/// instantly.
+const THINK_TIME: Duration = Duration::from_millis(1500);
+
+/// The maximum number of secrets a bot keeps track of. The formats that have
+/// more possible secrets than this are not supported by the bots.
+const MAX_CANDIDATES: usize = 200_000;
+
+/// The maximum number of scores a bot computes to choose a guess.
+const SCORE_BUDGET: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Difficulty {
+    /// Guesses a random secret that is consistent with the feedback so far.
+    #[default]
+    Easy,
+    /// Guesses the most informative of a few consistent secrets.
+    Medium,
+    /// Guesses the consistent secret that maximizes the expected information.
+    Hard,
+}
+
+/// A computer opponent, played by the server.
+///
+/// A bot acts like a client, it is notified like any other client, and it
+/// answers with directives when it is listened. So a bot can be a member of
+/// a lobby, or a player of a game, like a human does.
+pub struct Bot {
+    difficulty: Difficulty,
+    settings: Settings,
+    mind: Mind,
+    /// The guess being chosen on a blocking thread, with the mind it is
+    /// chosen with.
+    thinking: Option<JoinHandle<(Mind, Secret)>>,
+    /// When the guess being chosen can be sent.
+    ready_at: Instant,
+    pending: VecDeque<Directive>,
+    turn: bool,
+    rng: StdRng,
+}
+
+/// What a bot knows about the secret of the current game.
+///
+/// Building the solver and choosing a guess with it take too long to be done
+/// on the tasks of the server, so the mind is moved to a blocking thread
+/// while the bot thinks.
+#[derive(Default)]
+struct Mind {
+    /// The solver of the game, built the first time the bot guesses.
+    solver: Option<Solver>,
+    /// The feedback the solver hasn't recorded yet.
+    feedback: Vec<(Secret, Feedback)>,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty, settings: Settings) -> Self {
+        let mut rng = StdRng::from_entropy();
+        let secret = Secret::random(&settings.format, &mut rng);
+
+        Self {
+            difficulty,
+            settings,
+            mind: Mind::default(),
+            thinking: None,
+            ready_at: Instant::now(),
+            pending: VecDeque::from([Directive::SetSecret {
+                secret: SecretInput::Text(secret.to_string()),
+            }]),
+            turn: false,
+            rng,
+        }
+    }
+
+    /// Returns true if a bot can play with the given format.
+    pub fn supports(format: &Format) -> bool {
+        count_secrets(format) <= MAX_CANDIDATES
+    }
+
+    /// Waits for the next directive of the bot. If the bot has nothing to do,
+    /// the future never completes.
+    ///
+    /// The future can be dropped before it completes, the bot keeps thinking
+    /// about its guess until it is listened again, and the time it has
+    /// already waited counts.
+    pub async fn listen(&mut self) -> Directive {
+        if let Some(directive) = self.pending.pop_front() {
+            return directive;
+        }
+
+        if !self.turn {
+            return std::future::pending().await;
+        }
+
+        if self.thinking.is_none() {
+            self.thinking = Some(self.think());
+            self.ready_at = Instant::now() + THINK_TIME;
+        }
+
+        sleep_until(self.ready_at).await;
+
+        let thinking = self.thinking.as_mut().expect("The bot isn't thinking");
+        let (mind, guess) = thinking.await.expect("The bot panicked while thinking");
+
+        // Keep the feedback notified while the bot was thinking.
+        let feedback = std::mem::replace(&mut self.mind, mind).feedback;
+        self.mind.feedback.extend(feedback);
+        self.thinking = None;
+        self.turn = false;
+
+        Directive::Guess {
+            secret: SecretInput::Text(guess.to_string()),
+            target: None,
+        }
+    }
+
+    /// Starts choosing a guess on a blocking thread.
+    fn think(&mut self) -> JoinHandle<(Mind, Secret)> {
+        let mut mind = std::mem::take(&mut self.mind);
+        let mut rng = StdRng::seed_from_u64(self.rng.gen());
+        let difficulty = self.difficulty;
+        let settings = self.settings;
+
+        spawn_blocking(move || {
+            let guess = mind.guess(difficulty, &settings, &mut rng);
+            (mind, guess)
+        })
+    }
+
+    /// Updates the state of the bot with a notification sent to it.
+    pub fn observe(&mut self, notification: &Notification) {
+        use Notification::*;
+
+        match notification {
+            GameStart { .. } => {
+                self.mind = Mind::default();
+                self.thinking = None;
+                self.turn = false;
+            }
+            SettingsChanged { settings } => {
+                let format_changed = settings.format != self.settings.format;
+                self.settings = *settings;
+
+                // The secret of the bot is discarded by the lobby when the
+                // format changes, so it needs a new one.
+                if !Bot::supports(&settings.format) {
+                    self.pending.push_back(Directive::CloseConnection);
+                } else if format_changed {
+                    let secret = Secret::random(&settings.format, &mut self.rng);
+
+                    self.pending.push_back(Directive::SetSecret {
+                        secret: SecretInput::Text(secret.to_string()),
+                    });
+                }
+            }
+            NextTurn { .. } => self.turn = true,
+            GuessScore { secret, feedback } => {
+                self.mind.feedback.push(((*secret).clone(), feedback.clone()));
+            }
+            // A new secret is needed for the next game of the series.
+            GameEnd { series, .. } if !series.over => {
+                let secret = Secret::random(&self.settings.format, &mut self.rng);
+
+                self.pending.push_back(Directive::SetSecret {
+                    secret: SecretInput::Text(secret.to_string()),
+                });
+            }
+            // The bot has nothing to do when the series is over, or when it
+            // is left alone.
+            GameEnd { .. } | OpponentLeave => self.pending.push_back(Directive::CloseConnection),
+            _ => {}
+        }
+    }
+}
+
+impl Mind {
+    fn guess(&mut self, difficulty: Difficulty, settings: &Settings, rng: &mut StdRng) -> Secret {
+        let solver = self
+            .solver
+            .get_or_insert_with(|| Solver::new(&settings.format, settings.variant));
+
+        for (guess, feedback) in self.feedback.drain(..) {
+            solver.record(&guess, &feedback);
+        }
+
+        let candidates = solver.candidates();
+
+        let options = match difficulty {
+            Difficulty::Easy => 1,
+            Difficulty::Medium => 10,
+            Difficulty::Hard => SCORE_BUDGET / candidates.len().max(1),
+        };
+
+        let options: Vec<_> = candidates.choose_multiple(rng, options).collect();
+
+        let best = if options.len() > 1 {
+            solver.best(candidates, options, Strategy::Entropy)
+        } else {
+            options.first().copied()
+        };
+
+        match best {
+            Some(guess) => guess.clone(),
+            // The feedback was inconsistent, which means the bot was given a
+            // wrong score. Guessing randomly is the best it can do.
+            None => Secret::random(&settings.format, rng),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn solve(difficulty: Difficulty, settings: Settings) -> usize {
+        let mut mind = Mind::default();
+        let mut rng = StdRng::from_entropy();
+        let secret = Secret::random(&settings.format, &mut rng);
+        let rule = settings.variant.rule();
+
+        for guesses in 1.. {
+            let guess = mind.guess(difficulty, &settings, &mut rng);
+
+            if guess == secret {
+                return guesses;
+            }
+
+            let feedback = rule.score(&secret, &guess);
+            mind.feedback.push((guess, feedback));
+        }
+
+        unreachable!()
+    }
+
+    #[test]
+    fn supports_small_formats() {
+        assert!(Bot::supports(&Format::default()));
+        assert!(Bot::supports(&Format::new(6, false).unwrap()));
+        assert!(!Bot::supports(&Format::new(6, true).unwrap()));
+        assert!(!Bot::supports(&Format::new(10, false).unwrap()));
+    }
+
+    #[test]
+    fn sets_a_secret_first() {
+        let mut bot = Bot::new(Difficulty::Easy, Settings::default());
+
+        assert!(matches!(bot.pending.pop_front(), Some(Directive::SetSecret { .. })));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_thinking_when_the_listen_future_is_dropped() {
+        let mut bot = Bot::new(Difficulty::Easy, Settings::default());
+        bot.pending.clear();
+        bot.turn = true;
+
+        let start = Instant::now();
+
+        // A directive from the game interrupts the bot while it thinks.
+        let interrupted = tokio::time::timeout(THINK_TIME / 2, bot.listen()).await;
+        assert!(interrupted.is_err());
+
+        assert!(matches!(bot.listen().await, Directive::Guess { .. }));
+        assert_eq!(start.elapsed(), THINK_TIME);
+    }
+
+    #[test]
+    fn solves_secrets() {
+        for difficulty in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+            assert!(solve(difficulty, Settings::default()) <= 15);
+        }
+    }
+}
@@ -1,53 +1,250 @@
-use crate::{Directive, Notification};
+use crate::{
+    bot::Bot,
+    protocol::{self, LEGACY_VERSION},
+    message::ErrorCode,
+    secret::SecretError,
+    session::Session,
+    Directive, Notification,
+};
 use futures_util::{SinkExt, StreamExt};
-use tokio::net::TcpStream;
+use tokio::{net::TcpStream, select};
 use tokio_tungstenite::WebSocketStream;
 use tungstenite::{Error as TungsteniteError, Message};
 
 pub type ListenResult = Result<Directive, ListenError>;
 
-// TODO: The `WebSocket` type is about 300 bytes. And the code has a lot of
-// move semantics. Maybe it is better putting the inner socket behind a `Box`.
+/// The maximum length of a nickname in characters.
+pub const MAX_NICKNAME_LENGTH: usize = 24;
+
+/// A participant of the server, either a person connected through a websocket,
+/// or a bot played by the server.
 pub struct Client {
-    socket: WebSocketStream<TcpStream>,
+    connection: Connection,
+    identity: Identity,
+    /// Whether the socket has been replaced by a resumed connection that
+    /// hasn't been sent the state yet.
+    resumed: bool,
+}
+
+/// Who a client says they are, set by the `Hello` directive.
+#[derive(Debug)]
+struct Identity {
+    nickname: Option<String>,
+    client_version: Option<String>,
+    /// The version of the protocol the client speaks.
+    version: u32,
+}
+
+impl Default for Identity {
+    fn default() -> Self {
+        Self {
+            nickname: None,
+            client_version: None,
+            version: LEGACY_VERSION,
+        }
+    }
+}
+
+// The `WebSocket` type is about 300 bytes. And the code has a lot of move
+// semantics. So the inner socket is put behind a `Box`.
+enum Connection {
+    Socket(Box<WebSocketStream<TcpStream>>, Session),
+    Bot(Box<Bot>),
 }
 
 pub enum ListenError {
     SocketExhausted,
     InvalidMessage,
     UnknownMessage,
-    InvalidDirective,
+    /// The reason the message couldn't be parsed into a directive.
+    InvalidDirective(String),
 }
 
 impl Client {
     pub fn new(socket: WebSocketStream<TcpStream>) -> Self {
-        Self { socket }
+        Self {
+            connection: Connection::Socket(Box::new(socket), Session::new()),
+            identity: Identity::default(),
+            resumed: false,
+        }
+    }
+
+    pub fn bot(bot: Bot) -> Self {
+        Self {
+            connection: Connection::Bot(Box::new(bot)),
+            identity: Identity {
+                nickname: Some("Bot".to_owned()),
+                ..Identity::default()
+            },
+            resumed: false,
+        }
+    }
+
+    pub fn nickname(&self) -> Option<&str> {
+        self.identity.nickname.as_deref()
     }
 
+    pub fn client_version(&self) -> Option<&str> {
+        self.identity.client_version.as_deref()
+    }
+
+    pub fn version(&self) -> u32 {
+        self.identity.version
+    }
+
+    /// Sets the identity of the client. The surrounding whitespace of the
+    /// nickname is trimmed. Returns false if the nickname is empty, too long,
+    /// or has control characters, leaving the identity as it is.
+    pub fn identify(&mut self, nickname: &str, client_version: Option<String>, version: u32) -> bool {
+        let nickname = nickname.trim();
+
+        if !is_valid_nickname(nickname) {
+            return false;
+        }
+
+        self.identity = Identity {
+            nickname: Some(nickname.to_owned()),
+            client_version,
+            version,
+        };
+
+        true
+    }
+
+    pub fn is_bot(&self) -> bool {
+        matches!(self.connection, Connection::Bot(_))
+    }
+
+    /// The token of the session of the client, bots have none.
+    pub fn token(&self) -> Option<&str> {
+        match self.connection {
+            Connection::Socket(_, ref session) => Some(session.token()),
+            Connection::Bot(_) => None,
+        }
+    }
+
+    fn into_socket(self) -> Option<(Box<WebSocketStream<TcpStream>>, Identity)> {
+        match self.connection {
+            Connection::Socket(socket, _) => Some((socket, self.identity)),
+            Connection::Bot(_) => None,
+        }
+    }
+
+    /// Listens to the next directive of the client.
+    ///
+    /// When the socket drops, the session of the client is held for a while.
+    /// If a new connection resumes it in time, its socket takes the place of
+    /// the old one, and a `GetState` directive is returned so the listener
+    /// sends the state to the new connection. Otherwise the socket is
+    /// exhausted.
+    ///
+    /// The new connection keeps the nickname of the session, but it speaks
+    /// the version of the protocol it has negotiated, if any.
     pub async fn listen(&mut self) -> ListenResult {
         use ListenError::*;
 
-        let message = self
-            .socket
-            .next()
-            .await
-            .ok_or(SocketExhausted)?
-            .or(Err(InvalidMessage))?;
+        let message = loop {
+            // The state is still owed to the new connection if the future
+            // was dropped while it was notified.
+            if self.resumed {
+                let _ = self.notify(Notification::SessionResume).await;
+                self.resumed = false;
+
+                return Ok(Directive::GetState);
+            }
+
+            let (socket, session) = match self.connection {
+                Connection::Socket(ref mut socket, ref mut session) => (socket, session),
+                Connection::Bot(ref mut bot) => return Ok(bot.listen().await),
+            };
+
+            select! {
+                message = socket.next(), if !session.is_lost() => match message {
+                    Some(message) => break message.or(Err(InvalidMessage))?,
+                    None => session.lose(),
+                },
+                resumed = session.resumed() => match resumed.and_then(Client::into_socket) {
+                    Some((resumed, identity)) => {
+                        *socket = resumed;
+                        self.identity.client_version = identity.client_version;
+                        self.identity.version = identity.version;
+                        self.resumed = true;
+                    }
+                    None => return Err(SocketExhausted),
+                },
+            }
+        };
 
         match message {
-            Message::Text(ref text) => serde_json::from_str(text).or(Err(InvalidDirective)),
+            Message::Text(ref text) => serde_json::from_str(text).map_err(|error| InvalidDirective(error.to_string())),
             Message::Close(_) => Ok(Directive::CloseConnection),
             _ => Err(UnknownMessage),
         }
     }
 
     pub async fn notify(&mut self, n: Notification<'_>) -> Result<(), TungsteniteError> {
-        let json = serde_json::to_string(&n).expect("Couldn't parse notification to json");
+        match self.connection {
+            Connection::Socket(ref mut socket, _) => {
+                let json = protocol::to_json(self.identity.version, n);
+
+                socket.send(Message::Text(json)).await
+            }
+            Connection::Bot(ref mut bot) => {
+                bot.observe(&n);
+                Ok(())
+            }
+        }
+    }
+
+    /// Notifies the client that the directive is rejected.
+    pub async fn reject(&mut self, code: ErrorCode, directive: &'static str) {
+        self.reject_with(code, code.message(), directive).await;
+    }
+
+    /// Notifies the client that the directive is rejected, with a message
+    /// that tells the reason in detail.
+    pub async fn reject_with(&mut self, code: ErrorCode, message: &str, directive: &'static str) {
+        let _ = self
+            .notify(Notification::Error {
+                code,
+                message,
+                directive: Some(directive),
+            })
+            .await;
+    }
 
-        self.socket.send(Message::Text(json)).await
+    /// Notifies the client that the secret sent with the directive is
+    /// rejected, with the reason.
+    pub async fn reject_secret(&mut self, error: SecretError, directive: &'static str) {
+        self.reject_with(ErrorCode::InvalidSecret, &error.to_string(), directive).await;
+    }
+
+    /// Notifies the client of the error in its last message. Nothing is sent
+    /// if the socket is exhausted.
+    pub async fn report(&mut self, error: &ListenError) {
+        let (code, message) = match error {
+            ListenError::SocketExhausted => return,
+            ListenError::InvalidMessage => (ErrorCode::InvalidMessage, ErrorCode::InvalidMessage.message()),
+            ListenError::UnknownMessage => (ErrorCode::UnknownMessage, ErrorCode::UnknownMessage.message()),
+            ListenError::InvalidDirective(reason) => (ErrorCode::InvalidDirective, reason.as_str()),
+        };
+
+        let _ = self
+            .notify(Notification::Error {
+                code,
+                message,
+                directive: None,
+            })
+            .await;
     }
 }
 
+fn is_valid_nickname(nickname: &str) -> bool {
+    !nickname.is_empty()
+        && nickname.chars().count() <= MAX_NICKNAME_LENGTH
+        && !nickname.chars().any(char::is_control)
+}
+
 /// A utility enum type that wraps a client to be listened.
 ///
 /// The purpose of this enum is to wrap a client like an `Option` does, and to
@@ -88,7 +285,7 @@ pub trait Listener {
 
     /// If a client is being listened, returns the bundle of the client and a
     /// mutable reference to the listener, replaces the listener state with `Stop`.
-    fn bundle(&mut self) -> Option<Bundle<Self>>
+    fn bundle(&mut self) -> Option<Bundle<'_, Self>>
     where
         Self: Sized,
     {
@@ -98,6 +295,15 @@ pub trait Listener {
         })
     }
 
+    /// If a client is being listened, this method returns a reference to the
+    /// client.
+    fn client(&self) -> Option<&Client> {
+        match self.state() {
+            ListenerState::Listen(client) => Some(client),
+            ListenerState::Stop => None,
+        }
+    }
+
     /// If a client is being listened, this method returns a mutable reference
     /// to the client.
     fn client_mut(&mut self) -> Option<&mut Client> {
@@ -122,3 +328,17 @@ impl<L: Listener> Bundle<'_, L> {
         self.listener.attach(self.client);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validates_nicknames() {
+        assert!(is_valid_nickname("alice"));
+        assert!(is_valid_nickname("çağrı 42"));
+        assert!(!is_valid_nickname(""));
+        assert!(!is_valid_nickname("tab\there"));
+        assert!(!is_valid_nickname(&"a".repeat(MAX_NICKNAME_LENGTH + 1)));
+    }
+}
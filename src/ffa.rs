@@ -0,0 +1,219 @@
+use crate::{
+    client::{Client, Listener, ListenerState},
+    game::{Ending, Return},
+    message::ErrorCode,
+    spectate::{Event, Feed},
+    table::{Rules, Seat, Table},
+    Directive, Notification, Secret, Settings,
+};
+use futures_util::future::join_all;
+use tokio::sync::oneshot;
+
+/// A game of three or more players. The players take turns in the order of
+/// their seats, and each turn they guess the secret of one of the others. A
+/// player whose secret is found is eliminated, and the last player left wins.
+pub struct FreeForAll;
+
+pub struct Contender {
+    state: ListenerState,
+    secret: Secret,
+    /// The number of turns in a row the player has missed.
+    missed_turns: u32,
+    /// Whether the player is out of the game. The eliminated players are
+    /// still notified until the game ends.
+    eliminated: bool,
+}
+
+impl Listener for Contender {
+    fn state(&self) -> &ListenerState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut ListenerState {
+        &mut self.state
+    }
+}
+
+impl Seat for Contender {
+    fn missed_turns(&mut self) -> &mut u32 {
+        &mut self.missed_turns
+    }
+}
+
+impl FreeForAll {
+    pub fn spawn(
+        players: Vec<(Client, Secret)>,
+        settings: Settings,
+        lobby: oneshot::Sender<Return>,
+        feed: Feed,
+    ) {
+        let players = players
+            .into_iter()
+            .map(|(client, secret)| Contender {
+                state: ListenerState::Listen(client),
+                secret,
+                missed_turns: 0,
+                eliminated: false,
+            })
+            .collect();
+
+        let table = Table::new(players, settings, lobby, feed);
+
+        tokio::spawn(table.run(Self));
+    }
+
+    /// Takes the player out of the game, and reveals their secret to everyone.
+    /// If it is the turn of the player, the turn passes to the next player.
+    async fn eliminate(&mut self, table: &mut Table<Contender>, seat: usize, by: Option<usize>) {
+        if table.seats[seat].eliminated {
+            return;
+        }
+
+        table.seats[seat].eliminated = true;
+        table.feed.publish(Event::Eliminated { seat, by });
+
+        let secret = table.seats[seat].secret.clone();
+        table.notify_all(|| Notification::Eliminated { seat, by, secret: &secret }).await;
+
+        if seat == table.turn && alive(table) > 1 {
+            table.pass_turn(self).await;
+        }
+    }
+}
+
+/// The number of players who are still in the game.
+fn alive(table: &Table<Contender>) -> usize {
+    table.seats.iter().filter(|player| !player.eliminated).count()
+}
+
+/// The seat of the first player after the given seat who is still in the
+/// game, or the given seat itself if nobody else is left.
+fn next_alive(table: &Table<Contender>, seat: usize) -> usize {
+    let count = table.seats.len();
+
+    (1..count)
+        .map(|offset| (seat + offset) % count)
+        .find(|&next| !table.seats[next].eliminated)
+        .unwrap_or(seat)
+}
+
+impl Rules for FreeForAll {
+    type Seat = Contender;
+
+    fn is_over(&self, table: &Table<Contender>) -> bool {
+        alive(table) <= 1
+    }
+
+    fn can_guess(&self, table: &Table<Contender>, seat: usize) -> bool {
+        seat == table.turn && !table.seats[seat].eliminated
+    }
+
+    fn next_turn(&mut self, table: &Table<Contender>) -> Option<usize> {
+        Some(next_alive(table, table.turn))
+    }
+
+    async fn start(&mut self, table: &mut Table<Contender>, nicknames: &[Option<String>]) {
+        let players = table.seats.len();
+
+        join_all(table.seats.iter_mut().enumerate().map(|(seat, player)| {
+            player.client_mut().unwrap().notify(Notification::FreeForAllStart {
+                seat,
+                players,
+                nicknames: nicknames.iter().map(Option::as_deref).collect(),
+            })
+        }))
+        .await;
+    }
+
+    async fn guess(&mut self, table: &mut Table<Contender>, seat: usize, guess: Secret, target: Option<usize>) {
+        let target = match target {
+            Some(target) if target != seat && table.seats.get(target).is_some_and(|player| !player.eliminated) => {
+                target
+            }
+            Some(_) => return table.reject(seat, ErrorCode::InvalidTarget, "Guess").await,
+            None => next_alive(table, seat),
+        };
+
+        let secret = &table.seats[target].secret;
+        let feedback = table.settings.variant.rule().score(secret, &guess);
+        let solved = *secret == guess;
+
+        table.feed.publish(Event::Guess {
+            seat,
+            target: Some(target),
+            guess: guess.clone(),
+            feedback: feedback.clone(),
+        });
+
+        if let Some(client) = table.seats[seat].client_mut() {
+            let _ = client
+                .notify(Notification::TargetScore {
+                    target,
+                    secret: &guess,
+                    feedback,
+                })
+                .await;
+        }
+
+        if solved {
+            self.eliminate(table, target, Some(seat)).await;
+        }
+
+        if alive(table) > 1 {
+            table.pass_turn(self).await;
+        }
+    }
+
+    async fn forfeit(&mut self, table: &mut Table<Contender>, seat: usize, _: Ending) {
+        self.eliminate(table, seat, None).await;
+    }
+
+    async fn leave(&mut self, table: &mut Table<Contender>, seat: usize) {
+        self.eliminate(table, seat, None).await;
+    }
+
+    async fn handle(&mut self, table: &mut Table<Contender>, seat: usize, directive: Directive) {
+        match directive {
+            Directive::GetState => {
+                let eliminated = (0..table.seats.len())
+                    .filter(|&other| table.seats[other].eliminated)
+                    .collect();
+
+                let turn = table.turn;
+                let secret = table.seats[seat].secret.clone();
+
+                let client = table.seats[seat].client_mut().unwrap();
+                let _ = client
+                    .notify(Notification::FreeForAllState {
+                        seat,
+                        turn,
+                        secret: &secret,
+                        eliminated,
+                    })
+                    .await;
+            }
+            directive => table.reject(seat, ErrorCode::UnexpectedDirective, directive.name()).await,
+        }
+    }
+
+    /// Reveals every secret, and tells the winner if there is one.
+    async fn end(&mut self, table: &mut Table<Contender>) {
+        let winner = match alive(table) {
+            1 => table.seats.iter().position(|player| !player.eliminated),
+            _ => None,
+        };
+
+        table.feed.publish(Event::GameEnd { winner, ending: None });
+
+        let duration_ms = table.start.elapsed().as_millis() as u64;
+        let secrets: Vec<_> = table.seats.iter().map(|player| player.secret.clone()).collect();
+
+        table
+            .notify_all(|| Notification::FreeForAllEnd {
+                winner,
+                duration_ms,
+                secrets: secrets.iter().collect(),
+            })
+            .await;
+    }
+}
@@ -3,17 +3,96 @@ use crate::{
         Client, ListenError, ListenResult, Listener, Bundle,
         ListenerState,
     },
-    Notification, Directive, Idler, Secret,
+    hint::{self, Clue, HintKind},
+    message::ErrorCode,
+    scoring::Feedback,
+    series::Series,
+    settings::{Mode, TimeoutRule},
+    spectate::{Event, Feed},
+    Notification, Directive, Idler, Secret, Settings,
 };
 use log::debug;
+use serde::Serialize;
 use tokio::{
     select,
-    time::{interval, Duration, Interval},
+    sync::oneshot,
+    time::{sleep_until, Duration, Instant},
 };
 
+/// A guess of a player, the feedback given for it, and the time it was made
+/// since the start of the game.
+#[derive(Debug, Serialize)]
+pub struct Record {
+    pub guess: Secret,
+    #[serde(flatten)]
+    pub feedback: Feedback,
+    pub elapsed_ms: u64,
+}
+
+/// The result of a game for a player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Outcome {
+    Win,
+    Lose,
+    Draw,
+}
+
+impl Outcome {
+    /// The outcome of the same game for the opponent.
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Win => Self::Lose,
+            Self::Lose => Self::Win,
+            Self::Draw => Self::Draw,
+        }
+    }
+}
+
+/// The way a game has ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Ending {
+    /// A player has found the secret of the opponent.
+    Solved,
+    /// A player has left the game.
+    Forfeit,
+    /// A player has resigned.
+    Resign,
+    /// The players have agreed to a draw.
+    Agreement,
+    /// A player has lost the connection.
+    Disconnect,
+    /// A player has run out of time.
+    Timeout,
+    /// Both players have used up their guesses.
+    OutOfGuesses,
+    /// A player has missed too many turns in a row.
+    Idle,
+}
+
+/// The revealed secret and the guesses of a player, sent when a game ends.
+#[derive(Debug, Serialize)]
+pub struct Summary<'a> {
+    pub secret: &'a Secret,
+    pub guesses: usize,
+    pub history: &'a [Record],
+}
+
 pub struct Player {
     state: ListenerState,
     secret: Secret,
+    history: Vec<Record>,
+    /// The positions of the opponent's secret revealed by hints.
+    revealed: Vec<usize>,
+    /// The last guess that the player has been warned about in assist mode.
+    warned: Option<Secret>,
+    /// The number of turns in a row the player has missed.
+    missed_turns: u32,
+    /// Whether the player has offered a draw that isn't responded yet.
+    offers_draw: bool,
+    /// The guess of the player in the current round, in simultaneous mode.
+    locked: Option<Secret>,
+    /// Whether the player is the host of the lobby the game is started in.
+    host: bool,
 }
 
 impl Listener for Player {
@@ -31,13 +110,313 @@ impl Player {
         Self {
             state: ListenerState::Listen(client),
             secret,
+            history: Vec::new(),
+            revealed: Vec::new(),
+            warned: None,
+            missed_turns: 0,
+            offers_draw: false,
+            locked: None,
+            host: false,
+        }
+    }
+
+    fn summary(&self) -> Summary<'_> {
+        Summary {
+            secret: &self.secret,
+            guesses: self.history.len(),
+            history: &self.history,
+        }
+    }
+
+    fn out_of_guesses(&self, settings: &Settings) -> bool {
+        settings
+            .max_guesses
+            .is_some_and(|max| self.history.len() >= max as usize)
+    }
+
+    /// Returns true if the player is done with the current round, in
+    /// simultaneous mode.
+    fn is_ready(&self, settings: &Settings) -> bool {
+        self.locked.is_some() || self.out_of_guesses(settings)
+    }
+
+    /// Records the guess of the player in the current round, in simultaneous
+    /// mode. Returns whether the guess has found the secret, or `None` if the
+    /// player hasn't guessed in the round.
+    fn unlock(&mut self, secret: &Secret, context: &Context) -> Option<bool> {
+        let guess = self.locked.take()?;
+        let solved = guess == *secret;
+
+        self.record(guess, secret, context);
+
+        Some(solved)
+    }
+
+    /// Scores the guess, adds it to the history of the player, and publishes
+    /// it to the spectators.
+    fn record(&mut self, guess: Secret, secret: &Secret, context: &Context) {
+        let feedback = context.settings.variant.rule().score(secret, &guess);
+
+        context.feed.publish(Event::Guess {
+            seat: self.seat(),
+            target: None,
+            guess: guess.clone(),
+            feedback: feedback.clone(),
+        });
+
+        self.history.push(Record {
+            guess,
+            feedback,
+            elapsed_ms: context.start.elapsed().as_millis() as u64,
+        });
+    }
+
+    /// The seat of the player for the spectators. The host is in the first
+    /// seat.
+    fn seat(&self) -> usize {
+        usize::from(!self.host)
+    }
+
+    /// In assist mode, returns the earlier record that contradicts the guess,
+    /// unless the player has insisted on the guess by sending it again.
+    fn contradiction(&mut self, guess: &Secret, settings: &Settings) -> Option<&Record> {
+        if !settings.assist || self.warned.take().as_ref() == Some(guess) {
+            return None;
+        }
+
+        let earlier = self.history.iter().map(|record| (&record.guess, &record.feedback));
+        let index = hint::contradiction(settings.variant.rule(), guess, earlier)?;
+
+        self.warned = Some(guess.clone());
+        self.history.get(index)
+    }
+
+    fn hint(&self, kind: HintKind, secret: &Secret, settings: &Settings) -> Option<Clue> {
+        match kind {
+            HintKind::Digit => hint::reveal_digit(secret, &self.revealed),
+            HintKind::Consistency => {
+                let (last, earlier) = self.history.split_last()?;
+                let earlier = earlier.iter().map(|record| (&record.guess, &record.feedback));
+
+                Some(Clue::Consistency {
+                    consistent: hint::is_consistent(settings.variant.rule(), &last.guess, earlier),
+                })
+            }
+        }
+    }
+
+    /// Ends the game, records the result in the series, and notifies the
+    /// players with the result and the summaries of the game.
+    ///
+    /// If the game has ended because of a disconnect, the client of the
+    /// opponent is dropped. If the opponent has left the game, they go back
+    /// to idle.
+    async fn end(
+        mut player: Bundle<'_, Self>,
+        mut opponent: Bundle<'_, Self>,
+        outcome: Outcome,
+        ending: Ending,
+        context: &mut Context,
+    ) {
+        let duration_ms = context.start.elapsed().as_millis() as u64;
+        let host_outcome = if player.listener.host { outcome } else { outcome.opposite() };
+
+        context.series.record(host_outcome);
+        context.ended = true;
+
+        let winner = match outcome {
+            Outcome::Win => Some(player.listener.seat()),
+            Outcome::Lose => Some(opponent.listener.seat()),
+            Outcome::Draw => None,
+        };
+
+        context.feed.publish(Event::GameEnd { winner, ending: Some(ending) });
+
+        let player_notification = Notification::GameEnd {
+            outcome,
+            ending,
+            duration_ms,
+            player: player.listener.summary(),
+            opponent: opponent.listener.summary(),
+            series: context.series.standing(player.listener.host),
+        };
+
+        if ending == Ending::Disconnect {
+            let _ = player.client.notify(player_notification).await;
+        } else {
+            let _ = tokio::join! {
+                player.client.notify(player_notification),
+                opponent.client.notify(Notification::GameEnd {
+                    outcome: outcome.opposite(),
+                    ending,
+                    duration_ms,
+                    player: opponent.listener.summary(),
+                    opponent: player.listener.summary(),
+                    series: context.series.standing(opponent.listener.host),
+                }),
+            };
+
+            if ending == Ending::Forfeit {
+                Idler::spawn(opponent.client);
+            } else {
+                opponent.reunite();
+            }
         }
+
+        player.reunite();
     }
 
-    async fn on_leave(mut opponent: Bundle<'_, Self>) {
-        // Notify the opponent that the player has left.
-        let _ = opponent.client.notify(Notification::OpponentLeave).await;
-        Idler::spawn(opponent.client);
+    /// Passes the turn to the opponent. If the opponent is out of guesses, the
+    /// player keeps the turn instead.
+    async fn pass_turn<'a>(
+        player: &mut Bundle<'a, Self>,
+        opponent: &mut Bundle<'a, Self>,
+        context: &mut Context,
+    ) {
+        let next = if opponent.listener.out_of_guesses(&context.settings) {
+            context.turn.restart();
+            player
+        } else {
+            context.turn.next();
+            opponent
+        };
+
+        context.feed.publish(Event::NextTurn { seat: Some(next.listener.seat()) });
+
+        let _ = next
+            .client
+            .notify(Notification::NextTurn {
+                clocks: context.turn.clocks(),
+                missed_turns: next.listener.missed_turns,
+            })
+            .await;
+    }
+
+    async fn on_timeout(
+        mut player: Bundle<'_, Self>,
+        mut opponent: Bundle<'_, Self>,
+        context: &mut Context,
+    ) {
+        player.listener.missed_turns += 1;
+
+        let idle = context
+            .settings
+            .max_missed_turns
+            .is_some_and(|max| player.listener.missed_turns >= max);
+
+        // Running out of a chess clock always loses the game.
+        if context.turn.is_clocked() || context.settings.on_timeout == TimeoutRule::Forfeit {
+            Self::end(opponent, player, Outcome::Win, Ending::Timeout, context).await
+        } else if idle {
+            Self::end(opponent, player, Outcome::Win, Ending::Idle, context).await
+        } else {
+            Self::pass_turn(&mut player, &mut opponent, context).await;
+
+            player.reunite();
+            opponent.reunite();
+        }
+    }
+
+    /// Sends the score of the last guess to the player, if they have guessed.
+    async fn reveal(player: &mut Bundle<'_, Self>, guessed: bool) {
+        if let Some(record) = player.listener.history.last().filter(|_| guessed) {
+            let _ = player
+                .client
+                .notify(Notification::GuessScore {
+                    secret: &record.guess,
+                    feedback: record.feedback.clone(),
+                })
+                .await;
+        }
+    }
+
+    /// Ends a round in simultaneous mode, and reveals the scores of both
+    /// players at the same time.
+    async fn end_round(
+        mut player: Bundle<'_, Self>,
+        mut opponent: Bundle<'_, Self>,
+        context: &mut Context,
+    ) {
+        let player_solved = player.listener.unlock(&opponent.listener.secret, context);
+        let opponent_solved = opponent.listener.unlock(&player.listener.secret, context);
+
+        // Cracking the codes in the same round is a draw.
+        match (player_solved, opponent_solved) {
+            (Some(true), Some(true)) => {
+                return Self::end(player, opponent, Outcome::Draw, Ending::Solved, context).await
+            }
+            (Some(true), _) => {
+                return Self::end(player, opponent, Outcome::Win, Ending::Solved, context).await
+            }
+            (_, Some(true)) => {
+                return Self::end(opponent, player, Outcome::Win, Ending::Solved, context).await
+            }
+            _ => {}
+        }
+
+        let settings = &context.settings;
+
+        if player.listener.out_of_guesses(settings) && opponent.listener.out_of_guesses(settings) {
+            return Self::end(player, opponent, Outcome::Draw, Ending::OutOfGuesses, context).await;
+        }
+
+        // Only the players who have guessed in the round are scored.
+        let _ = tokio::join! {
+            Self::reveal(&mut player, player_solved.is_some()),
+            Self::reveal(&mut opponent, opponent_solved.is_some()),
+        };
+
+        context.turn.restart();
+        context.feed.publish(Event::NextTurn { seat: None });
+
+        let _ = tokio::join! {
+            player.client.notify(Notification::NextTurn {
+                clocks: None,
+                missed_turns: player.listener.missed_turns,
+            }),
+            opponent.client.notify(Notification::NextTurn {
+                clocks: None,
+                missed_turns: opponent.listener.missed_turns,
+            }),
+        };
+
+        player.reunite();
+        opponent.reunite();
+    }
+
+    /// Ends a round in simultaneous mode when its time is up. The players who
+    /// haven't guessed in the round miss their turn.
+    async fn on_round_timeout(
+        player: Bundle<'_, Self>,
+        opponent: Bundle<'_, Self>,
+        context: &mut Context,
+    ) {
+        let settings = &context.settings;
+
+        let loses = |player: &mut Player| {
+            if player.is_ready(settings) {
+                return false;
+            }
+
+            player.missed_turns += 1;
+
+            settings.on_timeout == TimeoutRule::Forfeit
+                || settings
+                    .max_missed_turns
+                    .is_some_and(|max| player.missed_turns >= max)
+        };
+
+        let ending = match settings.on_timeout {
+            TimeoutRule::Forfeit => Ending::Timeout,
+            TimeoutRule::Pass => Ending::Idle,
+        };
+
+        match (loses(player.listener), loses(opponent.listener)) {
+            (true, true) => Self::end(player, opponent, Outcome::Draw, ending, context).await,
+            (true, false) => Self::end(opponent, player, Outcome::Win, ending, context).await,
+            (false, true) => Self::end(player, opponent, Outcome::Win, ending, context).await,
+            (false, false) => Self::end_round(player, opponent, context).await,
+        }
     }
 
     async fn handle(
@@ -45,61 +424,213 @@ impl Player {
         mut player: Bundle<'_, Self>,
         mut opponent: Bundle<'_, Self>,
         can_guess: bool,
-        turn: &mut Turn,
+        context: &mut Context,
     ) {
         use Directive::*;
 
+        let settings = &context.settings;
+
         match result {
             Ok(directive) => {
                 match directive {
-                    Guess { secret } => {
+                    Guess { secret, .. } => {
                         if can_guess {
-                            let (correct, wrong) = opponent.listener.secret.score(&secret);
+                            let secret = match secret.parse(&settings.format) {
+                                Ok(secret) => secret,
+                                Err(error) => {
+                                    // A rejected guess doesn't use up the turn.
+                                    player.client.reject_secret(error, "Guess").await;
+
+                                    player.reunite();
+                                    opponent.reunite();
+
+                                    return;
+                                }
+                            };
+
+                            if let Some(record) = player.listener.contradiction(&secret, settings) {
+                                // The player may send the guess again to
+                                // insist on it, the turn isn't used up.
+                                let _ = player
+                                    .client
+                                    .notify(Notification::InconsistentGuess {
+                                        secret: &secret,
+                                        violates: record,
+                                    })
+                                    .await;
+                            } else if settings.mode == Mode::Simultaneous {
+                                player.listener.missed_turns = 0;
 
-                            if correct == 3 {
                                 let _ = tokio::join! {
-                                    player.client.notify(Notification::Win),
-                                    opponent.client.notify(Notification::Lose)
+                                    player.client.notify(Notification::GuessLocked { secret: &secret }),
+                                    opponent.client.notify(Notification::OpponentLocked),
                                 };
 
-                                Idler::spawn(player.client);
-                                Idler::spawn(opponent.client);
+                                player.listener.locked = Some(secret);
 
-                                return;
+                                if opponent.listener.is_ready(settings) {
+                                    return Self::end_round(player, opponent, context).await;
+                                }
                             } else {
-                                let _ = tokio::join! {
-                                    opponent.client.notify(Notification::NextTurn),
-                                    player.client.notify(Notification::GuessScore {
-                                        secret: &secret,
-                                        correct,
-                                        wrong
-                                    }),
-                                };
+                                let solved = opponent.listener.secret == secret;
+
+                                player.listener.missed_turns = 0;
+                                player.listener.record(secret, &opponent.listener.secret, context);
+
+                                if solved {
+                                    return Self::end(
+                                        player,
+                                        opponent,
+                                        Outcome::Win,
+                                        Ending::Solved,
+                                        context,
+                                    )
+                                    .await;
+                                }
 
-                                turn.next();
+                                if player.listener.out_of_guesses(settings)
+                                    && opponent.listener.out_of_guesses(settings)
+                                {
+                                    return Self::end(
+                                        player,
+                                        opponent,
+                                        Outcome::Draw,
+                                        Ending::OutOfGuesses,
+                                        context,
+                                    )
+                                    .await;
+                                }
+
+                                let record = player.listener.history.last().unwrap();
+
+                                let _ = player
+                                    .client
+                                    .notify(Notification::GuessScore {
+                                        secret: &record.guess,
+                                        feedback: record.feedback.clone(),
+                                    })
+                                    .await;
+
+                                Self::pass_turn(&mut player, &mut opponent, context).await;
                             }
+                        } else {
+                            player.client.reject(ErrorCode::NotYourTurn, "Guess").await;
                         }
 
                         player.reunite();
                         opponent.reunite();
                     }
-                    Leave => {
-                        Idler::spawn(player.client);
-                        Self::on_leave(opponent).await;
+                    RequestHint { kind } => {
+                        let clue = if can_guess && settings.mode == Mode::Alternating {
+                            player.listener.hint(kind, &opponent.listener.secret, settings)
+                        } else {
+                            None
+                        };
+
+                        if let Some(clue) = clue {
+                            if let Clue::Digit { position, .. } = clue {
+                                player.listener.revealed.push(position);
+                            }
+
+                            player.listener.missed_turns = 0;
+
+                            let _ = tokio::join! {
+                                player.client.notify(Notification::Hint { clue }),
+                                opponent.client.notify(Notification::OpponentHint { kind }),
+                            };
+
+                            // A hint costs the turn of the player.
+                            Self::pass_turn(&mut player, &mut opponent, context).await;
+                        } else {
+                            player.client.reject(ErrorCode::HintUnavailable, "RequestHint").await;
+                        }
+
+                        player.reunite();
+                        opponent.reunite();
+                    }
+                    GetHistory => {
+                        let _ = player
+                            .client
+                            .notify(Notification::History {
+                                player: &player.listener.history,
+                                opponent: &opponent.listener.history,
+                            })
+                            .await;
+
+                        player.reunite();
+                        opponent.reunite();
+                    }
+                    GetState => {
+                        let _ = player
+                            .client
+                            .notify(Notification::GameState {
+                                secret: &player.listener.secret,
+                                can_guess,
+                                clocks: context.turn.clocks_of(player.listener.host),
+                                missed_turns: player.listener.missed_turns,
+                                draw_offer: opponent.listener.offers_draw,
+                                player: &player.listener.history,
+                                opponent: &opponent.listener.history,
+                                series: context.series.standing(player.listener.host),
+                            })
+                            .await;
+
+                        player.reunite();
+                        opponent.reunite();
                     }
+                    Resign => Self::end(opponent, player, Outcome::Win, Ending::Resign, context).await,
+                    // Offering a draw when the opponent has already offered
+                    // one accepts it.
+                    OfferDraw | RespondDraw { accept: true } if opponent.listener.offers_draw => {
+                        Self::end(player, opponent, Outcome::Draw, Ending::Agreement, context).await
+                    }
+                    OfferDraw => {
+                        if player.listener.offers_draw {
+                            player
+                                .client
+                                .reject_with(
+                                    ErrorCode::UnexpectedDirective,
+                                    "a draw is already offered",
+                                    "OfferDraw",
+                                )
+                                .await;
+                        } else {
+                            player.listener.offers_draw = true;
+                            let _ = opponent.client.notify(Notification::DrawOffer).await;
+                        }
+
+                        player.reunite();
+                        opponent.reunite();
+                    }
+                    RespondDraw { accept: false } => {
+                        if opponent.listener.offers_draw {
+                            opponent.listener.offers_draw = false;
+                            let _ = opponent.client.notify(Notification::DrawDecline).await;
+                        } else {
+                            player.client.reject(ErrorCode::UnexpectedDirective, "RespondDraw").await;
+                        }
+
+                        player.reunite();
+                        opponent.reunite();
+                    }
+                    Leave => Self::end(opponent, player, Outcome::Win, Ending::Forfeit, context).await,
                     CloseConnection => {
-                        Self::on_leave(opponent).await;
+                        Self::end(opponent, player, Outcome::Win, Ending::Disconnect, context).await
                     }
-                    _ => {
+                    directive => {
+                        player.client.reject(ErrorCode::UnexpectedDirective, directive.name()).await;
+
                         player.reunite();
                         opponent.reunite();
                     }
                 }
             }
             Err(ListenError::SocketExhausted) => {
-                Self::on_leave(opponent).await;
+                Self::end(opponent, player, Outcome::Win, Ending::Disconnect, context).await
             }
-            _ => {
+            Err(error) => {
+                player.client.report(&error).await;
+
                 player.reunite();
                 opponent.reunite();
             }
@@ -107,22 +638,83 @@ impl Player {
     }
 }
 
+/// Keeps track of whose turn it is, and when the current turn ends.
 pub struct Turn {
     record: bool,
-    interval: Interval,
+    started: Instant,
+    deadline: Instant,
+    duration: Duration,
+    clock: Option<ChessClock>,
+}
+
+/// The clocks of the players, indexed by the turn record.
+struct ChessClock {
+    remaining: [Duration; 2],
+    increment: Duration,
+}
+
+/// The remaining clock times, from the point of view of a player.
+#[derive(Debug, Serialize)]
+pub struct Clocks {
+    pub player_ms: u64,
+    pub opponent_ms: u64,
 }
 
 impl Turn {
-    fn new(duration: u64) -> Self {
-        Self {
-            record: false,
-            interval: interval(Duration::from_secs(duration)),
+    fn new(settings: &Settings, host_first: bool) -> Self {
+        let now = Instant::now();
+
+        let clock = settings.clock.map(|clock| ChessClock {
+            remaining: [Duration::from_secs(clock.budget_secs); 2],
+            increment: Duration::from_secs(clock.increment_secs),
+        });
+
+        let mut turn = Self {
+            record: host_first,
+            started: now,
+            deadline: now,
+            duration: Duration::from_secs(settings.turn_secs),
+            clock,
+        };
+
+        turn.start(now);
+        turn
+    }
+
+    /// Starts the turn of the current player. With a chess clock, the turn
+    /// lasts until the clock of the player runs out.
+    fn start(&mut self, now: Instant) {
+        let duration = match self.clock {
+            Some(ref clock) => clock.remaining[self.record as usize],
+            None => self.duration,
+        };
+
+        self.started = now;
+        self.deadline = now + duration;
+    }
+
+    /// Stops the clock of the current player, and adds the increment.
+    fn stop(&mut self, now: Instant) {
+        if let Some(ref mut clock) = self.clock {
+            let remaining = &mut clock.remaining[self.record as usize];
+            *remaining = remaining.saturating_sub(now - self.started) + clock.increment;
         }
     }
 
     fn next(&mut self) {
+        let now = Instant::now();
+
+        self.stop(now);
         self.record = !self.record;
-        self.interval.reset();
+        self.start(now);
+    }
+
+    /// Starts the current turn over.
+    fn restart(&mut self) {
+        let now = Instant::now();
+
+        self.stop(now);
+        self.start(now);
     }
 
     fn of_host(&self) -> bool {
@@ -133,23 +725,105 @@ impl Turn {
         !self.record
     }
 
-    async fn interval_tick(&mut self) {
-        self.interval.tick().await;
+    fn is_clocked(&self) -> bool {
+        self.clock.is_some()
+    }
+
+    /// The clocks from the point of view of the player whose turn it is.
+    fn clocks(&self) -> Option<Clocks> {
+        self.clocks_of(self.record)
     }
+
+    /// The clocks from the point of view of the host or the guest. The clock
+    /// of the current player runs down during the turn.
+    fn clocks_of(&self, host: bool) -> Option<Clocks> {
+        self.clock.as_ref().map(|clock| {
+            let remaining = |record: bool| {
+                let remaining = clock.remaining[record as usize];
+
+                let remaining = if record == self.record {
+                    remaining.saturating_sub(self.started.elapsed())
+                } else {
+                    remaining
+                };
+
+                remaining.as_millis() as u64
+            };
+
+            Clocks {
+                player_ms: remaining(host),
+                opponent_ms: remaining(!host),
+            }
+        })
+    }
+
+    /// Completes when the time of the current turn is up.
+    async fn timeout(&self) {
+        sleep_until(self.deadline).await;
+    }
+}
+
+/// The state of a game that is shared by the handlers of the players.
+pub struct Context {
+    turn: Turn,
+    settings: Settings,
+    start: Instant,
+    series: Series,
+    /// Whether the game has ended, with the clients still attached to the
+    /// players.
+    ended: bool,
+    feed: Feed,
+}
+
+impl Context {
+    /// Returns true if the player can guess. In alternating mode, a player
+    /// guesses in their turn, in simultaneous mode, once in every round.
+    fn can_guess(&self, player: &Player, in_turn: bool) -> bool {
+        match self.settings.mode {
+            Mode::Alternating => in_turn,
+            Mode::Simultaneous => !player.is_ready(&self.settings),
+        }
+    }
+}
+
+/// The clients sent back to the lobby when a game ends, in the order of the
+/// members of the lobby, along with the series the game is a part of. The
+/// client of a player who has left is `None`.
+pub struct Return {
+    pub clients: Vec<Option<Client>>,
+    pub series: Option<Series>,
 }
 
 pub struct Game {
     host: Player,
     guest: Player,
-    turn: Turn,
+    context: Context,
+    lobby: oneshot::Sender<Return>,
 }
 
 impl Game {
-    pub fn spawn(host: Player, guest: Player) {
+    pub fn spawn(
+        mut host: Player,
+        guest: Player,
+        settings: Settings,
+        series: Series,
+        lobby: oneshot::Sender<Return>,
+        feed: Feed,
+    ) {
+        host.host = true;
+
         let game = Self {
             host,
             guest,
-            turn: Turn::new(20),
+            context: Context {
+                turn: Turn::new(&settings, series.host_first()),
+                settings,
+                start: Instant::now(),
+                series,
+                ended: false,
+                feed,
+            },
+            lobby,
         };
 
         tokio::spawn(game.listen());
@@ -158,34 +832,300 @@ impl Game {
     pub async fn listen(mut self) {
         debug!("Listening to player directives in a game");
 
+        let series = self.context.series;
+        let [host_nickname, guest_nickname] = [&self.host, &self.guest]
+            .map(|player| player.client().and_then(Client::nickname).map(str::to_owned));
+
         let _ = tokio::join! {
-            self.host.client_mut().unwrap().notify(Notification::GameStart),
-            self.guest.client_mut().unwrap().notify(Notification::GameStart)
+            self.host.client_mut().unwrap().notify(Notification::GameStart {
+                series: series.standing(true),
+                opponent: guest_nickname.as_deref(),
+            }),
+            self.guest.client_mut().unwrap().notify(Notification::GameStart {
+                series: series.standing(false),
+                opponent: host_nickname.as_deref(),
+            }),
         };
 
-        while let (Some(mut host), Some(mut guest)) = (self.host.bundle(), self.guest.bundle()) {
-            select! {
-                _ = self.turn.interval_tick() => {
-                    self.turn.next();
+        let next_turn = || Notification::NextTurn {
+            clocks: self.context.turn.clocks(),
+            missed_turns: 0,
+        };
 
-                    let _ = if self.turn.of_host() {
-                        host.client.notify(Notification::NextTurn).await
-                    } else {
-                        guest.client.notify(Notification::NextTurn).await
-                    };
+        self.context.feed.publish(Event::GameStart {
+            nicknames: vec![host_nickname, guest_nickname],
+        });
+
+        // Let the player who moves first know that it is their turn. In
+        // simultaneous mode, both players move in the first round.
+        if self.context.settings.mode == Mode::Simultaneous {
+            self.context.feed.publish(Event::NextTurn { seat: None });
 
-                    host.reunite();
-                    guest.reunite();
+            let _ = tokio::join! {
+                self.host.client_mut().unwrap().notify(next_turn()),
+                self.guest.client_mut().unwrap().notify(next_turn()),
+            };
+        } else {
+            let first = if self.context.turn.of_host() { &mut self.host } else { &mut self.guest };
+            self.context.feed.publish(Event::NextTurn { seat: Some(first.seat()) });
+            let _ = first.client_mut().unwrap().notify(next_turn()).await;
+        }
+
+        while !self.context.ended {
+            let (Some(mut host), Some(mut guest)) = (self.host.bundle(), self.guest.bundle()) else {
+                break;
+            };
+
+            select! {
+                _ = self.context.turn.timeout() => {
+                    if self.context.settings.mode == Mode::Simultaneous {
+                        Player::on_round_timeout(host, guest, &mut self.context).await;
+                    } else if self.context.turn.of_host() {
+                        Player::on_timeout(host, guest, &mut self.context).await;
+                    } else {
+                        Player::on_timeout(guest, host, &mut self.context).await;
+                    }
                 },
                 result = host.client.listen() => {
-                    Player::handle(result, host, guest, self.turn.of_host(), &mut self.turn).await;
+                    let can_guess = self.context.can_guess(host.listener, self.context.turn.of_host());
+                    Player::handle(result, host, guest, can_guess, &mut self.context).await;
                 },
                 result = guest.client.listen() => {
-                    Player::handle(result, guest, host, self.turn.of_guest(), &mut self.turn).await;
+                    let can_guess = self.context.can_guess(guest.listener, self.context.turn.of_guest());
+                    Player::handle(result, guest, host, can_guess, &mut self.context).await;
                 },
             }
         }
 
+        let back = Return {
+            clients: vec![self.host.take(), self.guest.take()],
+            series: Some(self.context.series),
+        };
+
+        // The lobby waits for the players as long as the game runs, but if
+        // it is gone anyway, the players go back to idle.
+        if let Err(back) = self.lobby.send(back) {
+            back.clients.into_iter().flatten().for_each(Idler::spawn);
+        }
+
         debug!("Dropping a game listener");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        bot::{Bot, Difficulty},
+        settings::Clock,
+    };
+    use tokio::{sync::broadcast, time};
+
+    /// The players of a game between two bots, the host moves first.
+    fn players(settings: &Settings) -> (Player, Player) {
+        let player = |secret, host| {
+            let client = Client::bot(Bot::new(Difficulty::Easy, *settings));
+            let mut player = Player::new(client, Secret::parse(secret).unwrap());
+            player.host = host;
+            player
+        };
+
+        (player("123", true), player("456", false))
+    }
+
+    fn context(settings: Settings) -> Context {
+        Context {
+            turn: Turn::new(&settings, true),
+            settings,
+            start: Instant::now(),
+            series: Series::new(1),
+            ended: false,
+            feed: Feed::new(broadcast::channel(16).0, 0),
+        }
+    }
+
+    #[test]
+    fn starts_with_the_player_who_moves_first() {
+        let settings = Settings::default();
+        let series = Series::new(3);
+
+        let turn = Turn::new(&settings, series.host_first());
+        assert!(turn.of_host());
+
+        let turn = Turn::new(&settings, false);
+        assert!(turn.of_guest());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_the_clock_of_the_player_in_turn() {
+        let settings = Settings {
+            clock: Some(Clock {
+                budget_secs: 10,
+                increment_secs: 2,
+            }),
+            ..Settings::default()
+        };
+
+        let mut turn = Turn::new(&settings, true);
+        assert_eq!(turn.deadline, Instant::now() + Duration::from_secs(10));
+
+        // Only the clock of the host runs down in their turn.
+        time::advance(Duration::from_secs(3)).await;
+
+        let clocks = turn.clocks_of(true).unwrap();
+        assert_eq!((clocks.player_ms, clocks.opponent_ms), (7_000, 10_000));
+
+        let clocks = turn.clocks_of(false).unwrap();
+        assert_eq!((clocks.player_ms, clocks.opponent_ms), (10_000, 7_000));
+
+        // The host gets the increment when their turn ends, and the clock of
+        // the guest starts.
+        turn.next();
+        assert!(turn.of_guest());
+        assert_eq!(turn.deadline, Instant::now() + Duration::from_secs(10));
+
+        time::advance(Duration::from_secs(4)).await;
+
+        let clocks = turn.clocks().unwrap();
+        assert_eq!((clocks.player_ms, clocks.opponent_ms), (6_000, 9_000));
+
+        // Starting the turn over stops the clock, and gives the increment.
+        turn.restart();
+        assert!(turn.of_guest());
+        assert_eq!(turn.deadline, Instant::now() + Duration::from_secs(8));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_every_turn_the_same_time_without_a_clock() {
+        let settings = Settings::default();
+        let mut turn = Turn::new(&settings, true);
+
+        assert!(!turn.is_clocked());
+        assert!(turn.clocks().is_none());
+
+        time::advance(Duration::from_secs(5)).await;
+        turn.next();
+
+        assert_eq!(turn.deadline, Instant::now() + Duration::from_secs(settings.turn_secs));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn forfeits_after_too_many_missed_turns() {
+        let settings = Settings {
+            max_missed_turns: Some(2),
+            ..Settings::default()
+        };
+
+        let (mut host, mut guest) = players(&settings);
+        let mut context = context(settings);
+
+        // The first missed turn passes the turn to the guest.
+        Player::on_timeout(host.bundle().unwrap(), guest.bundle().unwrap(), &mut context).await;
+        assert!(!context.ended);
+        assert!(context.turn.of_guest());
+        assert_eq!(host.missed_turns, 1);
+
+        // The guest plays, and the host misses their turn again.
+        context.turn.next();
+        Player::on_timeout(host.bundle().unwrap(), guest.bundle().unwrap(), &mut context).await;
+        assert!(context.ended);
+        assert_eq!(context.series.standing(true).losses, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn forfeits_the_first_missed_turn_when_asked() {
+        let clocked = Settings {
+            clock: Some(Clock {
+                budget_secs: 60,
+                increment_secs: 0,
+            }),
+            ..Settings::default()
+        };
+        let forfeit = Settings {
+            on_timeout: TimeoutRule::Forfeit,
+            ..Settings::default()
+        };
+
+        for settings in [clocked, forfeit] {
+            let (mut host, mut guest) = players(&settings);
+            let mut context = context(settings);
+
+            Player::on_timeout(host.bundle().unwrap(), guest.bundle().unwrap(), &mut context).await;
+            assert!(context.ended);
+            assert_eq!(context.series.standing(true).losses, 1);
+        }
+    }
+
+    fn simultaneous() -> Settings {
+        Settings {
+            mode: Mode::Simultaneous,
+            ..Settings::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn draws_when_both_solve_in_the_same_round() {
+        let (mut host, mut guest) = players(&simultaneous());
+        let mut context = context(simultaneous());
+
+        host.locked = Some(guest.secret.clone());
+        guest.locked = Some(host.secret.clone());
+
+        Player::end_round(host.bundle().unwrap(), guest.bundle().unwrap(), &mut context).await;
+        assert!(context.ended);
+        assert_eq!(context.series.standing(true).draws, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn scores_both_guesses_of_a_round() {
+        let (mut host, mut guest) = players(&simultaneous());
+        let mut context = context(simultaneous());
+
+        host.locked = Some(Secret::parse("789").unwrap());
+        guest.locked = Some(Secret::parse("789").unwrap());
+
+        Player::end_round(host.bundle().unwrap(), guest.bundle().unwrap(), &mut context).await;
+        assert!(!context.ended);
+        assert_eq!((host.history.len(), guest.history.len()), (1, 1));
+        assert!(host.locked.is_none() && guest.locked.is_none());
+
+        // Only the host solves in the next round.
+        host.locked = Some(guest.secret.clone());
+        guest.locked = Some(Secret::parse("789").unwrap());
+
+        Player::end_round(host.bundle().unwrap(), guest.bundle().unwrap(), &mut context).await;
+        assert!(context.ended);
+        assert_eq!(context.series.standing(true).wins, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ends_a_round_when_its_time_is_up() {
+        let (mut host, mut guest) = players(&simultaneous());
+        let mut context = context(simultaneous());
+
+        // The guest misses the round, and the guess of the host is scored.
+        host.locked = Some(Secret::parse("789").unwrap());
+
+        Player::on_round_timeout(host.bundle().unwrap(), guest.bundle().unwrap(), &mut context).await;
+        assert!(!context.ended);
+        assert_eq!((host.history.len(), guest.history.len()), (1, 0));
+        assert_eq!((host.missed_turns, guest.missed_turns), (0, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn forfeits_a_missed_round_when_asked() {
+        let settings = Settings {
+            on_timeout: TimeoutRule::Forfeit,
+            ..simultaneous()
+        };
+
+        let (mut host, mut guest) = players(&settings);
+        let mut context = context(settings);
+
+        host.locked = Some(Secret::parse("789").unwrap());
+
+        Player::on_round_timeout(host.bundle().unwrap(), guest.bundle().unwrap(), &mut context).await;
+        assert!(context.ended);
+        assert_eq!(context.series.standing(true).wins, 1);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    scoring::{Feedback, ScoringRule},
+    Secret,
+};
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
+
+/// The kind of a hint a player asks for, at the cost of their turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HintKind {
+    /// Reveals a digit of the opponent's secret.
+    Digit,
+    /// Tells whether the last guess was consistent with the earlier feedback.
+    Consistency,
+}
+
+/// The information revealed by a hint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "hint")]
+pub enum Clue {
+    Digit { position: usize, digit: u8 },
+    Consistency { consistent: bool },
+}
+
+/// Reveals a random digit of the secret, other than the ones in `revealed`.
+/// Returns `None` if every digit is already revealed.
+pub fn reveal_digit(secret: &Secret, revealed: &[usize]) -> Option<Clue> {
+    let digits = secret.digits();
+
+    (0..digits.len())
+        .filter(|position| !revealed.contains(position))
+        .choose(&mut rand::thread_rng())
+        .map(|position| Clue::Digit {
+            position,
+            digit: digits[position],
+        })
+}
+
+/// Returns true if the guess could be the secret, given the feedback of the
+/// earlier guesses. In other words, if the guess were the secret, every
+/// earlier guess would have been given the same feedback.
+pub fn is_consistent<'a, I>(rule: &dyn ScoringRule, guess: &Secret, earlier: I) -> bool
+where
+    I: IntoIterator<Item = (&'a Secret, &'a Feedback)>,
+{
+    contradiction(rule, guess, earlier).is_none()
+}
+
+/// Returns the index of the first earlier guess whose feedback contradicts
+/// the guess, if there is any.
+pub fn contradiction<'a, I>(rule: &dyn ScoringRule, guess: &Secret, earlier: I) -> Option<usize>
+where
+    I: IntoIterator<Item = (&'a Secret, &'a Feedback)>,
+{
+    earlier
+        .into_iter()
+        .position(|(earlier, feedback)| rule.score(guess, earlier) != *feedback)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scoring::Variant;
+
+    fn secret(text: &str) -> Secret {
+        Secret::parse(text).unwrap()
+    }
+
+    #[test]
+    fn reveals_unrevealed_digits() {
+        let secret = secret("123");
+
+        assert_eq!(
+            reveal_digit(&secret, &[0, 2]),
+            Some(Clue::Digit { position: 1, digit: 2 })
+        );
+        assert_eq!(reveal_digit(&secret, &[0, 1, 2]), None);
+    }
+
+    #[test]
+    fn checks_consistency() {
+        let rule = Variant::Classic.rule();
+        let first = secret("123");
+        let feedback = Feedback::BullsAndCows { correct: 1, wrong: 0 };
+        let earlier = [(&first, &feedback)];
+
+        assert!(is_consistent(rule, &secret("145"), earlier));
+        assert!(!is_consistent(rule, &secret("456"), earlier));
+        assert!(!is_consistent(rule, &secret("312"), earlier));
+    }
+
+    #[test]
+    fn finds_the_first_contradiction() {
+        let rule = Variant::Classic.rule();
+        let (first, second) = (secret("123"), secret("456"));
+        let first_feedback = Feedback::BullsAndCows { correct: 0, wrong: 1 };
+        let second_feedback = Feedback::BullsAndCows { correct: 0, wrong: 0 };
+        let earlier = [(&first, &first_feedback), (&second, &second_feedback)];
+
+        assert_eq!(contradiction(rule, &secret("789"), earlier), Some(0));
+        assert_eq!(contradiction(rule, &secret("345"), earlier), Some(1));
+        assert_eq!(contradiction(rule, &secret("390"), earlier), None);
+    }
+}
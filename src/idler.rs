@@ -1,6 +1,9 @@
 use crate::{
-    client::{Client, ListenError, Listener, ListenerState},
-    Directive, Lobby,
+    client::{Client, ListenError, Listener, ListenerState, MAX_NICKNAME_LENGTH},
+    message::ErrorCode,
+    protocol::{self, Capabilities},
+    session::Session,
+    Directive, Lobby, Notification, Solo,
 };
 use log::debug;
 
@@ -31,15 +34,68 @@ impl Idler {
             match client.listen().await {
                 Ok(directive) => match directive {
                     // Because the client is moved, the state remains `Stop`
-                    // for the two arms below
-                    CreateLobby => Lobby::spawn(client),
+                    // for the four arms below
+                    CreateLobby { settings } => Lobby::spawn(client, settings),
                     JoinLobby { lobby_id } => Lobby::send(lobby_id, client).await,
+                    Spectate { lobby_id } => Lobby::spectate(lobby_id, client).await,
+                    StartSolo { settings } => Solo::spawn(client, settings),
+
+                    Hello { nickname, client_version, versions } => {
+                        match protocol::negotiate(&versions) {
+                            Some(version) if client.identify(&nickname, client_version, version) => {
+                                let _ = client
+                                    .notify(Notification::Welcome {
+                                        nickname: nickname.trim(),
+                                        version,
+                                        capabilities: Capabilities::new(),
+                                    })
+                                    .await;
+                            }
+                            Some(_) => {
+                                let message = format!(
+                                    "a nickname must be 1 to {} characters, without control characters",
+                                    MAX_NICKNAME_LENGTH
+                                );
+
+                                client
+                                    .reject_with(ErrorCode::InvalidNickname, &message, "Hello")
+                                    .await;
+                            }
+                            None => {
+                                let message = format!(
+                                    "the server supports the versions {:?} of the protocol",
+                                    protocol::VERSIONS
+                                );
+
+                                client
+                                    .reject_with(ErrorCode::UnsupportedVersion, &message, "Hello")
+                                    .await;
+                            }
+                        }
+
+                        self.attach(client);
+                    }
+
+                    // The connection of the client takes the place of the
+                    // connection of the session, and this client is dropped.
+                    Resume { token } => {
+                        if let Err(mut client) = Session::resume(&token, client) {
+                            client.reject(ErrorCode::ResumeFailed, "Resume").await;
+                            self.attach(client);
+                        }
+                    }
 
                     // The state remains `Stop` so the client gets dropped.
                     CloseConnection => {}
 
-                    // Continue listening only if the directive is ignored.
-                    _ => self.attach(client),
+                    // An idle client has no state to show.
+                    GetState => self.attach(client),
+
+                    // Continue listening only if the directive is rejected.
+                    directive => {
+                        client.reject(ErrorCode::UnexpectedDirective, directive.name()).await;
+                        self.attach(client);
+                    }
                 },
 
                 // Cannot read the socket, the state remains `Stop`,
@@ -47,7 +103,10 @@ impl Idler {
                 Err(ListenError::SocketExhausted) => {}
 
                 // Continue listening
-                _ => self.attach(client),
+                Err(error) => {
+                    client.report(&error).await;
+                    self.attach(client);
+                }
             }
         }
 
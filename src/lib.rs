@@ -1,15 +1,27 @@
-#![feature(once_cell)]
-#![feature(entry_insert)]
-
+pub mod bot;
 pub mod client;
+pub mod ffa;
 pub mod game;
+pub mod hint;
 pub mod idler;
 pub mod lobby;
 pub mod message;
+pub mod protocol;
+pub mod scoring;
 pub mod secret;
+pub mod series;
+pub mod session;
+pub mod settings;
+pub mod solo;
+pub mod solver;
+pub mod spectate;
+pub mod table;
+pub mod team;
 
 pub use game::{Game, Player};
 pub use idler::Idler;
 pub use lobby::{Lobby, LobbyId};
 pub use message::{Directive, Notification};
 pub use secret::Secret;
+pub use settings::Settings;
+pub use solo::Solo;
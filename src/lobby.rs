@@ -1,8 +1,18 @@
 use crate::{
+    bot::Bot,
     client::{Client, ListenError, ListenResult, Listener, ListenerState, Bundle},
-    Directive, Game, Idler, Notification, Player, Secret,
+    ffa::FreeForAll,
+    game::Return,
+    message::{ErrorCode, MAX_CHAT_LENGTH},
+    series::Series,
+    spectate::{Feed, Release, Spectator, FEED_CAPACITY},
+    team::TeamGame,
+    Directive, Game, Idler, Notification, Player, Secret, Settings,
+};
+use futures_util::{
+    future::{join_all, select_all},
+    FutureExt,
 };
-use futures_util::future::OptionFuture;
 use log::{debug, warn};
 use std::{
     collections::HashMap,
@@ -14,32 +24,96 @@ use std::{
 };
 use tokio::{
     select,
-    sync::mpsc::{channel, Receiver, Sender},
+    sync::{
+        broadcast,
+        mpsc::{channel, Receiver, Sender},
+        oneshot,
+    },
 };
 
 pub type LobbyId = usize;
-type LobbyIndex = Arc<RwLock<HashMap<LobbyId, Sender<Client>>>>;
+type LobbyIndex = Arc<RwLock<HashMap<LobbyId, Sender<Arrival>>>>;
+
+/// A client sent to a lobby, either to join it or to watch its games.
+enum Arrival {
+    Member(Client),
+    Spectator(Client),
+}
+
+impl Arrival {
+    fn into_client(self) -> Client {
+        match self {
+            Self::Member(client) | Self::Spectator(client) => client,
+        }
+    }
+
+    /// Turns the client away with the error, and sends it back to idle.
+    async fn reject(self, code: ErrorCode) {
+        let directive = match self {
+            Self::Member(_) => "JoinLobby",
+            Self::Spectator(_) => "Spectate",
+        };
+
+        let mut client = self.into_client();
+        client.reject(code, directive).await;
+
+        Idler::spawn(client);
+    }
+}
 
 static LOBBIES: LazyLock<LobbyIndex> = LazyLock::new(|| Arc::new(RwLock::new(HashMap::new())));
 
+/// A lobby lasts across the games of its members. While a game is running,
+/// the clients of the members are moved into the game, and they come back to
+/// the lobby when the game ends.
 pub struct Lobby {
+    context: Context,
+    /// The members of the lobby in the order they have joined. The first
+    /// member is the host, who chooses the settings and starts the games.
+    members: Vec<Member>,
+    /// Receives the members back from the running game, if there is one.
+    game: Option<oneshot::Receiver<Return>>,
+    /// Sends the events of the games to the spectators.
+    feed: broadcast::Sender<Release>,
+}
+
+/// The state of a lobby that is shared by the handlers of the members.
+struct Context {
     id: LobbyId,
-    host: Host,
-    guest: Guest,
+    settings: Settings,
+    /// The series that is running in the lobby, if there is one. The games
+    /// of a running series start as soon as every member sets a secret.
+    series: Option<Series>,
+    /// Whether the host has asked to start a game.
+    start: bool,
 }
 
 impl Lobby {
-    fn new(creator: Client) -> Self {
+    fn new(creator: Client, settings: Settings) -> Self {
         static ID: AtomicUsize = AtomicUsize::new(0);
 
         Self {
-            id: ID.fetch_add(1, Ordering::Relaxed),
-            host: Host::new(creator),
-            guest: Guest::new(),
+            context: Context {
+                id: ID.fetch_add(1, Ordering::Relaxed),
+                settings,
+                series: None,
+                start: false,
+            },
+            members: vec![Member::new(creator, 0)],
+            game: None,
+            feed: broadcast::channel(FEED_CAPACITY).0,
         }
     }
 
     pub async fn send(id: LobbyId, client: Client) {
+        Self::deliver(id, Arrival::Member(client)).await;
+    }
+
+    pub async fn spectate(id: LobbyId, client: Client) {
+        Self::deliver(id, Arrival::Spectator(client)).await;
+    }
+
+    async fn deliver(id: LobbyId, arrival: Arrival) {
         // Try to acquire the Sender of the lobby of the corresponding id.
         let client_sender = {
             LOBBIES
@@ -49,196 +123,333 @@ impl Lobby {
                 .cloned()
         };
 
-        if let Some(sender) = client_sender {
-            if let Err(error) = sender.send(client).await {
-                // If the send was unsuccessful, spawn an idle handler for
-                // the client.
-                Idler::spawn(error.0);
-
-                // This may be an unwanted behavior, so logging a warning
-                // might be a good indicator (for the future).
-                warn!("Couln't send the client through the lobby sender.");
-            } else {
-                debug!("A member has just been sent to a lobby");
-            }
+        let Some(sender) = client_sender else {
+            return arrival.reject(ErrorCode::LobbyNotFound).await;
+        };
+
+        if let Err(error) = sender.send(arrival).await {
+            // The lobby is gone since its sender is acquired, spawn an idle
+            // handler for the client.
+            error.0.reject(ErrorCode::LobbyNotFound).await;
+
+            // This may be an unwanted behavior, so logging a warning
+            // might be a good indicator (for the future).
+            warn!("Couln't send the client through the lobby sender.");
+        } else {
+            debug!("A client has just been sent to a lobby");
         }
     }
 
-    pub fn spawn(creator: Client) {
+    pub fn spawn(creator: Client, settings: Settings) {
         let (sender, receiver) = channel(1);
-        let lobby = Lobby::new(creator);
+        let lobby = Lobby::new(creator, settings);
 
         {
             LOBBIES
                 .write()
                 .expect("Error acquiring the lobby index lock")
-                .entry(lobby.id)
+                .entry(lobby.context.id)
                 .insert_entry(sender);
         }
 
         tokio::spawn(lobby.listen(receiver));
     }
 
-    async fn listen(mut self, mut receiver: Receiver<Client>) {
+    async fn listen(mut self, mut receiver: Receiver<Arrival>) {
         debug!("Listening to member directives in a lobby");
 
-        let _ = self
-            .host
+        let _ = self.members[0]
             .client_mut()
             .unwrap()
-            .notify(Notification::LobbyCreate { lobby_id: self.id })
+            .notify(Notification::LobbyCreate {
+                lobby_id: self.context.id,
+                settings: self.context.settings,
+            })
             .await;
 
-        // A lobby is guaranteed to have a host connected. Therefore the lobby
-        // task must live as long as the host is being listened. A `while let`
-        // is handy for this case.
-        while let Some(mut host) = self.host.bundle() {
-            // A guest may or may not to be connected. Also, a guest must be
-            // listened with a host concurrently. Thus, they must be put into the
-            // same select body. The `OptionFuture` utility allows listening a
-            // possibly connected guest along with the host. If there is a guest,
-            // and the guest sends a directive, the future below will return
-            // Some(result).
-            let guest_listen_future: OptionFuture<_> = self
-                .guest
+        if self.context.settings.teams {
+            let _ = self.members[0]
                 .client_mut()
-                .map(|client| client.listen())
-                .into();
-
-            // Here, the `host_client` has already been moved from its listener,
-            // and the `guest_client` will be moved if it is relevant.
-            //
-            // Handler functions will attach the clients to the appropriate listeners
-            // if necessarry. So no need to attach them here by hand.
-            select! {
-                result = host.client.listen() => {
-                    Host::handle(result, host, &mut self.guest).await;
-                }
-                Some(result) = guest_listen_future => {
-                    let guest_bundle = self.guest.bundle().unwrap();
-                    Guest::handle(result, guest_bundle, &mut host.client).await;
-                    host.reunite();
+                .unwrap()
+                .notify(Notification::TeamJoin { team: 0 })
+                .await;
+        }
+
+        loop {
+            // While a game is running, nobody else can join the lobby.
+            if let Some(game) = self.game.as_mut() {
+                select! {
+                    back = game => {
+                        self.game = None;
+
+                        match back {
+                            Ok(back) => self.on_game_end(back),
+                            // The clients are lost with the game.
+                            Err(_) => self.members.clear(),
+                        }
+                    }
+                    Some(arrival) = receiver.recv() => match arrival {
+                        arrival @ Arrival::Member(_) => {
+                            arrival.reject(ErrorCode::GameRunning).await;
+                            debug!("Member join rejected, a game is running");
+                        }
+                        Arrival::Spectator(client) => self.on_spectate(client).await,
+                    },
                 }
-                Some(mut client) = receiver.recv() => {
-                    // If there is already a guest, spawn an idle handler for
-                    // the incoming client.
-                    if self.guest.is_listening() {
-                        Idler::spawn(client);
-                        debug!("Guest join rejected, the lobby is full");
-                    } else {
-                        let _ = tokio::join!{
-                            host.client.notify(Notification::GuestJoin),
-                            client.notify(Notification::LobbyJoin { lobby_id: self.id }),
-                        };
 
-                        self.guest.attach(client);
-                        host.reunite();
+                continue;
+            }
+
+            // Otherwise a lobby is guaranteed to have a host connected.
+            // Therefore the lobby task must live as long as there are members.
+            if self.members.is_empty() {
+                break;
+            }
+
+            // Every member of the lobby is listened at the same time. The
+            // `select_all` utility returns the result of the first member who
+            // sends a directive, along with the index of the member.
+            let members_listen_future = select_all(
+                self.members
+                    .iter_mut()
+                    .map(|member| Box::pin(member.client_mut().unwrap().listen())),
+            )
+            .map(|(result, index, _)| (result, index));
+
+            select! {
+                (result, index) = members_listen_future => {
+                    // The member is moved out of the list while it is handled,
+                    // and it is put back only if it is still listened.
+                    let mut member = self.members.remove(index);
+                    let bundle = member.bundle().unwrap();
 
-                        debug!("Guest join accepted");
+                    Member::handle(result, bundle, &mut self.members, index, &mut self.context).await;
+
+                    if member.is_listening() {
+                        self.members.insert(index, member);
                     }
+                }
+                Some(arrival) = receiver.recv() => match arrival {
+                    Arrival::Member(client) => self.on_join(client).await,
+                    Arrival::Spectator(client) => self.on_spectate(client).await,
                 },
             }
+
+            self.try_start();
         }
 
         {
             LOBBIES
                 .write()
                 .expect("Error acquiring the lobby index lock")
-                .remove(&self.id);
+                .remove(&self.context.id);
         }
 
         debug!("Dropping a lobby listener");
     }
-}
 
-struct Host {
-    state: ListenerState,
-    secret: Option<Secret>,
-}
+    async fn on_join(&mut self, mut client: Client) {
+        // If the lobby is full, spawn an idle handler for the incoming
+        // client.
+        if self.members.len() >= self.context.settings.players as usize {
+            Arrival::Member(client).reject(ErrorCode::LobbyFull).await;
+            debug!("Member join rejected, the lobby is full");
 
-impl Listener for Host {
-    fn state(&self) -> &ListenerState {
-        &self.state
-    }
+            return;
+        }
 
-    fn state_mut(&mut self) -> &mut ListenerState {
-        &mut self.state
-    }
-}
+        let nickname = client.nickname().map(str::to_owned);
+        let members: Vec<_> = self
+            .members
+            .iter()
+            .map(|member| member.client().and_then(Client::nickname).map(str::to_owned))
+            .collect();
+
+        let _ = tokio::join!{
+            notify_all(&mut self.members, || Notification::GuestJoin {
+                nickname: nickname.as_deref(),
+            }),
+            client.notify(Notification::LobbyJoin {
+                lobby_id: self.context.id,
+                settings: self.context.settings,
+                members: members.iter().map(Option::as_deref).collect(),
+            }),
+        };
 
-impl Host {
-    fn new(client: Client) -> Self {
-        Self {
-            state: ListenerState::Listen(client),
-            secret: None,
+        // A new member joins the smaller team.
+        let team = smaller_team(&self.members);
+
+        let mut member = Member::new(client, team);
+
+        // The new member shares the secret of the team.
+        if self.context.settings.teams {
+            let _ = member.client_mut().unwrap().notify(Notification::TeamJoin { team }).await;
+            member.secret = team_secret(&self.members, team);
         }
+
+        self.members.push(member);
+
+        debug!("Member join accepted");
+    }
+
+    /// Lets the client watch the games of the lobby, including the running
+    /// one.
+    async fn on_spectate(&mut self, mut client: Client) {
+        let _ = client
+            .notify(Notification::SpectateStart {
+                lobby_id: self.context.id,
+                settings: self.context.settings,
+            })
+            .await;
+
+        Spectator::spawn(client, self.feed.subscribe());
+
+        debug!("A spectator joined a lobby");
     }
 
-    async fn on_start_game(host: Bundle<'_, Host>, guest: &mut Guest) {
-        if !(host.listener.secret.is_some() && guest.secret.is_some()) {
-            return host.reunite();
+    /// Starts a game if the host has asked for it, or a series is running,
+    /// and every member has set a secret. In team mode, the teams play
+    /// against each other if they are of the same size. Otherwise two members
+    /// play a duel, and more members play a free-for-all.
+    fn try_start(&mut self) {
+        let start = std::mem::take(&mut self.context.start) || self.context.series.is_some();
+
+        if !(start && self.members.len() >= 2 && self.members.iter().all(|member| member.secret.is_some())) {
+            return;
         }
 
-        if let Some(guest_client) = guest.take() {
-            let host = Player::new(host.client, host.listener.secret.take().unwrap());
-            let guest = Player::new(guest_client, guest.secret.take().unwrap());
+        let settings = self.context.settings;
 
-            Game::spawn(host, guest);
-        } else {
-            host.reunite();
+        // The teams must be of the same size, with at least two players each.
+        let first_team = self.members.iter().filter(|member| member.team == 0).count();
+
+        if settings.teams && (first_team * 2 != self.members.len() || first_team < 2) {
+            return;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let feed = Feed::new(self.feed.clone(), settings.spectator_delay_secs);
+
+        if settings.teams {
+            let secrets = [0, 1].map(|team| {
+                self.members
+                    .iter()
+                    .find(|member| member.team == team)
+                    .and_then(|member| member.secret.clone())
+                    .unwrap()
+            });
+
+            let players = self
+                .members
+                .iter_mut()
+                .map(|member| {
+                    member.secret = None;
+                    (member.take().unwrap(), member.team)
+                })
+                .collect();
+
+            TeamGame::spawn(players, secrets, settings, sender, feed);
+            self.game = Some(receiver);
+
+            return;
         }
-    }
 
-    async fn on_leave(host: &mut Host, guest: &mut Guest) {
-        // When the host leaves, if there is a guest, the guest becomes the host.
-        if let Some(mut client) = guest.take() {
-            let _ = client.notify(Notification::OpponentLeave).await;
+        let players: Vec<_> = self
+            .members
+            .iter_mut()
+            .map(|member| (member.take().unwrap(), member.secret.take().unwrap()))
+            .collect();
 
-            // Attach guest's listener to the host member.
-            host.attach(client);
+        match <[_; 2]>::try_from(players) {
+            Ok([(host, host_secret), (guest, guest_secret)]) => {
+                let series = *self
+                    .context
+                    .series
+                    .get_or_insert_with(|| Series::new(settings.best_of));
 
-            // Move guest's secret to the host.
-            host.secret = guest.secret.take();
+                let host = Player::new(host, host_secret);
+                let guest = Player::new(guest, guest_secret);
+
+                Game::spawn(host, guest, settings, series, sender, feed);
+            }
+            Err(players) => FreeForAll::spawn(players, settings, sender, feed),
         }
+
+        self.game = Some(receiver);
     }
 
-    async fn handle(
-        result: ListenResult,
-        mut host: Bundle<'_, Host>,
-        guest: &mut Guest,
-    ) {
-        use Directive::*;
+    /// Takes the members back when a game ends. The members who have left the
+    /// game are gone, and the first member left becomes the host. A bot
+    /// doesn't stay in a lobby without a human member.
+    fn on_game_end(&mut self, back: Return) {
+        let complete = back.clients.iter().all(Option::is_some);
 
-        match result {
-            Ok(directive) => match directive {
-                SetSecret { secret } => {
-                    let _ = host.client
-                        .notify(Notification::SecretSet { secret: &secret })
-                        .await;
+        // A series can't go on without all of its players.
+        self.context.series = back
+            .series
+            .filter(|series| complete && !series.is_over());
 
-                    host.listener.secret = Some(secret);
-                    host.reunite();
-                }
-                StartGame => Self::on_start_game(host, guest).await,
-                Leave => {
-                    Self::on_leave(host.listener, guest).await;
-                    Idler::spawn(host.client);
-                }
-                CloseConnection => Self::on_leave(host.listener, guest).await,
-                _ => host.reunite(),
-            },
-            Err(ListenError::SocketExhausted) => Self::on_leave(host.listener, guest).await,
-            _ => host.reunite(),
+        let mut clients = back.clients.into_iter();
+
+        self.members.retain_mut(|member| match clients.next().flatten() {
+            Some(client) => {
+                member.attach(client);
+                true
+            }
+            None => false,
+        });
+
+        if self
+            .members
+            .iter()
+            .all(|member| member.client().is_some_and(Client::is_bot))
+        {
+            self.members.clear();
         }
     }
 }
 
-struct Guest {
+/// Returns the team with fewer members, or the first team if the teams are
+/// of the same size.
+fn smaller_team(members: &[Member]) -> usize {
+    let first = members.iter().filter(|member| member.team == 0).count();
+
+    (first * 2 > members.len()) as usize
+}
+
+/// Returns the secret of the team, if it is set.
+fn team_secret(members: &[Member], team: usize) -> Option<Secret> {
+    members
+        .iter()
+        .find(|member| member.team == team)
+        .and_then(|member| member.secret.clone())
+}
+
+/// Notifies every member in the list.
+async fn notify_all<'a, F>(members: &mut [Member], notification: F)
+where
+    F: Fn() -> Notification<'a>,
+{
+    join_all(
+        members
+            .iter_mut()
+            .filter_map(|member| member.client_mut())
+            .map(|client| client.notify(notification())),
+    )
+    .await;
+}
+
+struct Member {
     state: ListenerState,
+    /// The secret of the member, or the secret of the team in team mode.
     secret: Option<Secret>,
+    /// Whether the member has asked for a rematch.
+    rematch: bool,
+    /// The team of the member, in team mode.
+    team: usize,
 }
 
-impl Listener for Guest {
+impl Listener for Member {
     fn state(&self) -> &ListenerState {
         &self.state
     }
@@ -248,45 +459,299 @@ impl Listener for Guest {
     }
 }
 
-impl Guest {
-    fn new() -> Self {
+impl Member {
+    fn new(client: Client, team: usize) -> Self {
         Self {
-            state: ListenerState::Stop,
+            state: ListenerState::Listen(client),
             secret: None,
+            rematch: false,
+            team,
         }
     }
 
-    async fn on_leave(guest: &mut Guest, host: &mut Client) {
-        guest.secret = None;
-        let _ = host.notify(Notification::OpponentLeave).await;
+    async fn on_leave(others: &mut [Member], context: &mut Context) {
+        // The series can't go on without the member. When the host leaves,
+        // the member who has joined first becomes the host.
+        context.series = None;
+
+        for other in others.iter_mut() {
+            other.rematch = false;
+        }
+
+        notify_all(others, || Notification::OpponentLeave).await;
     }
 
     async fn handle(
         result: ListenResult,
-        mut guest: Bundle<'_, Guest>,
-        host: &mut Client,
+        mut member: Bundle<'_, Member>,
+        others: &mut Vec<Member>,
+        index: usize,
+        context: &mut Context,
     ) {
         use Directive::*;
 
+        // The first member is the host.
+        let host = index == 0;
+
+        let settings = &mut context.settings;
+
         match result {
             Ok(directive) => match directive {
                 SetSecret { secret } => {
-                    let _ = guest.client
-                        .notify(Notification::SecretSet { secret: &secret })
+                    match secret.parse(&settings.format) {
+                        Ok(secret) => {
+                            let notification = || Notification::SecretSet { secret: &secret };
+
+                            // In team mode, the secret is shared by the team.
+                            if settings.teams {
+                                let team = member.listener.team;
+                                let mut teammates: Vec<_> = others
+                                    .iter_mut()
+                                    .filter(|other| other.team == team)
+                                    .collect();
+
+                                for teammate in teammates.iter_mut() {
+                                    teammate.secret = Some(secret.clone());
+                                }
+
+                                join_all(
+                                    teammates
+                                        .into_iter()
+                                        .filter_map(|teammate| teammate.client_mut())
+                                        .map(|client| client.notify(notification())),
+                                )
+                                .await;
+                            }
+
+                            let _ = member.client.notify(notification()).await;
+
+                            member.listener.secret = Some(secret);
+                        }
+                        Err(error) => member.client.reject_secret(error, "SetSecret").await,
+                    }
+
+                    member.reunite();
+                }
+                StartGame if host => {
+                    let secrets_set = member.listener.secret.is_some()
+                        && others.iter().all(|other| other.secret.is_some());
+
+                    let first_team = others
+                        .iter()
+                        .map(|other| other.team)
+                        .chain([member.listener.team])
+                        .filter(|&team| team == 0)
+                        .count();
+
+                    if others.is_empty() {
+                        member.client.reject(ErrorCode::NotEnoughPlayers, "StartGame").await;
+                    } else if !secrets_set {
+                        member.client.reject(ErrorCode::SecretsNotSet, "StartGame").await;
+                    } else if settings.teams && first_team * 2 != others.len() + 1 {
+                        member.client.reject(ErrorCode::UnevenTeams, "StartGame").await;
+                    } else if settings.teams && first_team < 2 {
+                        member
+                            .client
+                            .reject_with(
+                                ErrorCode::NotEnoughPlayers,
+                                "a team game needs at least two players in each team",
+                                "StartGame",
+                            )
+                            .await;
+                    } else {
+                        context.start = true;
+                    }
+
+                    member.reunite();
+                }
+                UpdateSettings { update } if host => {
+                    match settings.update(&update) {
+                        Err(error) => {
+                            let message = error.to_string();
+
+                            member
+                                .client
+                                .reject_with(ErrorCode::InvalidSettings, &message, "UpdateSettings")
+                                .await;
+                        }
+                        // The lobby can't be shrunk below its members.
+                        Ok(updated) if updated.players as usize <= others.len() => {
+                            member.client.reject(ErrorCode::TooManyMembers, "UpdateSettings").await;
+                        }
+                        // A bot only plays in a lobby for two, so a team game
+                        // can't be set either.
+                        Ok(updated)
+                            if updated.players > 2
+                                && others.iter().any(|other| other.client().is_some_and(Client::is_bot)) =>
+                        {
+                            member
+                                .client
+                                .reject_with(
+                                    ErrorCode::BotUnavailable,
+                                    "a bot only plays in a lobby for two",
+                                    "UpdateSettings",
+                                )
+                                .await;
+                        }
+                        Ok(updated) => {
+                            // The secrets may be invalid with a different format.
+                            if updated.format != settings.format {
+                                member.listener.secret = None;
+
+                                for other in others.iter_mut() {
+                                    other.secret = None;
+                                }
+                            }
+
+                            // The members may be in the same team when the team mode
+                            // is turned on, so the teams are chosen again.
+                            if updated.teams && !settings.teams {
+                                member.listener.team = 0;
+                                member.listener.secret = None;
+
+                                for (index, other) in others.iter_mut().enumerate() {
+                                    other.team = (index + 1) % 2;
+                                    other.secret = None;
+                                }
+                            }
+
+                            *settings = updated;
+
+                            // A running series is played with the same settings.
+                            context.series = None;
+
+                            let notification = || Notification::SettingsChanged { settings: updated };
+
+                            let _ = tokio::join! {
+                                member.client.notify(notification()),
+                                notify_all(others, notification),
+                            };
+
+                            if updated.teams {
+                                let _ = tokio::join! {
+                                    member.client.notify(Notification::TeamJoin { team: member.listener.team }),
+                                    join_all(others.iter_mut().map(|other| {
+                                        let team = other.team;
+                                        other.client_mut().unwrap().notify(Notification::TeamJoin { team })
+                                    })),
+                                };
+                            }
+                        }
+                    }
+
+                    member.reunite();
+                }
+                AddBot { difficulty } if host => {
+                    // A bot takes a vacant place in a lobby for two.
+                    if settings.players == 2 && others.is_empty() && Bot::supports(&settings.format) {
+                        let bot = Client::bot(Bot::new(difficulty, *settings));
+
+                        let _ = member
+                            .client
+                            .notify(Notification::GuestJoin { nickname: bot.nickname() })
+                            .await;
+
+                        others.push(Member::new(bot, 1));
+
+                        debug!("A bot joined the lobby");
+                    } else {
+                        member.client.reject(ErrorCode::BotUnavailable, "AddBot").await;
+                    }
+
+                    member.reunite();
+                }
+                // A new series starts when every member has asked for it.
+                Rematch if context.series.is_none() && !others.is_empty() => {
+                    if others.iter().all(|other| other.rematch) {
+                        context.series = Some(Series::new(settings.best_of));
+
+                        for other in others.iter_mut() {
+                            other.rematch = false;
+                        }
+
+                        let _ = tokio::join! {
+                            member.client.notify(Notification::RematchAccept),
+                            notify_all(others, || Notification::RematchAccept),
+                        };
+                    } else if member.listener.rematch {
+                        member
+                            .client
+                            .reject_with(
+                                ErrorCode::UnexpectedDirective,
+                                "a rematch is already offered",
+                                "Rematch",
+                            )
+                            .await;
+                    } else {
+                        member.listener.rematch = true;
+                        notify_all(others, || Notification::RematchOffer).await;
+                    }
+
+                    member.reunite();
+                }
+                // A member moves to the other team, and takes its secret.
+                SwitchTeam if settings.teams => {
+                    let team = 1 - member.listener.team;
+
+                    member.listener.team = team;
+                    member.listener.secret = team_secret(others, team);
+
+                    let _ = member.client.notify(Notification::TeamJoin { team }).await;
+
+                    member.reunite();
+                }
+                TeamChat { message } if settings.teams && message.len() > MAX_CHAT_LENGTH => {
+                    member.client.reject(ErrorCode::ChatTooLong, "TeamChat").await;
+                    member.reunite();
+                }
+                TeamChat { message } if settings.teams => {
+                    let team = member.listener.team;
+
+                    join_all(
+                        others
+                            .iter_mut()
+                            .filter(|other| other.team == team)
+                            .filter_map(|other| other.client_mut())
+                            .map(|client| client.notify(Notification::TeamChat { seat: index, message: &message })),
+                    )
+                    .await;
+
+                    member.reunite();
+                }
+                GetState => {
+                    let _ = member
+                        .client
+                        .notify(Notification::LobbyState {
+                            lobby_id: context.id,
+                            settings: *settings,
+                            members: others.len() + 1,
+                            host,
+                            secret: member.listener.secret.as_ref(),
+                            team: settings.teams.then_some(member.listener.team),
+                        })
                         .await;
 
-                    guest.listener.secret = Some(secret);
-                    guest.reunite();
+                    member.reunite();
                 }
                 Leave => {
-                    Self::on_leave(guest.listener, host).await;
-                    Idler::spawn(guest.client);
+                    Self::on_leave(others, context).await;
+                    Idler::spawn(member.client);
+                }
+                CloseConnection => Self::on_leave(others, context).await,
+                directive @ (StartGame | UpdateSettings { .. } | AddBot { .. }) => {
+                    member.client.reject(ErrorCode::NotHost, directive.name()).await;
+                    member.reunite();
+                }
+                directive => {
+                    member.client.reject(ErrorCode::UnexpectedDirective, directive.name()).await;
+                    member.reunite();
                 }
-                CloseConnection => Self::on_leave(guest.listener, host).await,
-                _ => guest.reunite(),
             },
-            Err(ListenError::SocketExhausted) => Self::on_leave(guest.listener, host).await,
-            _ => guest.reunite(),
+            Err(ListenError::SocketExhausted) => Self::on_leave(others, context).await,
+            Err(error) => {
+                member.client.report(&error).await;
+                member.reunite();
+            }
         }
     }
 }
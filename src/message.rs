@@ -1,31 +1,381 @@
-use crate::{LobbyId, Secret};
+use crate::{
+    bot::Difficulty,
+    game::{Clocks, Ending, Outcome, Record, Summary},
+    hint::{Clue, HintKind},
+    protocol::Capabilities,
+    scoring::Feedback,
+    secret::SecretInput,
+    series::Standing,
+    session::Token,
+    settings::SettingsUpdate,
+    spectate::Event,
+    LobbyId, Secret, Settings,
+};
 use serde::{Deserialize, Serialize};
 
+/// The maximum length of a chat message in bytes.
+pub const MAX_CHAT_LENGTH: usize = 500;
+
 #[non_exhaustive]
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 pub enum Directive {
+    /// Introduces the client. It can be sent again to change the nickname.
+    Hello {
+        nickname: String,
+        #[serde(default)]
+        client_version: Option<String>,
+        /// The versions of the protocol the client supports. A client that
+        /// declares none speaks the legacy version.
+        #[serde(default)]
+        versions: Vec<u32>,
+    },
     CloseConnection,
-    CreateLobby,
+    CreateLobby {
+        #[serde(flatten)]
+        settings: Settings,
+    },
     JoinLobby { lobby_id: LobbyId },
+    Spectate { lobby_id: LobbyId },
     Leave,
-    SetSecret { secret: Secret },
+    SetSecret { secret: SecretInput },
     StartGame,
-    Guess { secret: Secret },
+    /// Changes the settings of the lobby, the missing fields are kept.
+    UpdateSettings {
+        #[serde(flatten)]
+        update: SettingsUpdate,
+    },
+    AddBot {
+        #[serde(default)]
+        difficulty: Difficulty,
+    },
+    Guess {
+        secret: SecretInput,
+        /// The seat of the player whose secret is guessed, in a free-for-all
+        /// game. If it isn't given, the next player in the turn order is the
+        /// target.
+        #[serde(default)]
+        target: Option<usize>,
+    },
+    RequestHint { kind: HintKind },
+    GetHistory,
+    Resign,
+    OfferDraw,
+    RespondDraw { accept: bool },
+    Rematch,
+    SwitchTeam,
+    TeamChat { message: String },
+    StartSolo {
+        #[serde(flatten)]
+        settings: Settings,
+    },
+    /// Takes the place of the connection of an earlier session.
+    Resume { token: Token },
+    /// Asks for the state of the lobby or the game the client is in.
+    GetState,
+}
+
+impl Directive {
+    /// The type of the directive, as it is sent by the clients.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Hello { .. } => "Hello",
+            Self::CloseConnection => "CloseConnection",
+            Self::CreateLobby { .. } => "CreateLobby",
+            Self::JoinLobby { .. } => "JoinLobby",
+            Self::Spectate { .. } => "Spectate",
+            Self::Leave => "Leave",
+            Self::SetSecret { .. } => "SetSecret",
+            Self::StartGame => "StartGame",
+            Self::UpdateSettings { .. } => "UpdateSettings",
+            Self::AddBot { .. } => "AddBot",
+            Self::Guess { .. } => "Guess",
+            Self::RequestHint { .. } => "RequestHint",
+            Self::GetHistory => "GetHistory",
+            Self::Resign => "Resign",
+            Self::OfferDraw => "OfferDraw",
+            Self::RespondDraw { .. } => "RespondDraw",
+            Self::Rematch => "Rematch",
+            Self::SwitchTeam => "SwitchTeam",
+            Self::TeamChat { .. } => "TeamChat",
+            Self::StartSolo { .. } => "StartSolo",
+            Self::Resume { .. } => "Resume",
+            Self::GetState => "GetState",
+        }
+    }
+}
+
+/// The reason a directive is rejected. The codes are stable, so the clients
+/// can rely on them, unlike the messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ErrorCode {
+    /// The websocket message couldn't be read.
+    InvalidMessage,
+    /// The websocket message isn't a text message.
+    UnknownMessage,
+    /// The text message isn't a valid directive.
+    InvalidDirective,
+    /// The directive isn't available in the current state of the client.
+    UnexpectedDirective,
+    InvalidNickname,
+    UnsupportedVersion,
+    ResumeFailed,
+    InvalidSecret,
+    NotHost,
+    NotYourTurn,
+    NotEnoughPlayers,
+    SecretsNotSet,
+    UnevenTeams,
+    LobbyNotFound,
+    LobbyFull,
+    GameRunning,
+    TooManyMembers,
+    InvalidSettings,
+    BotUnavailable,
+    HintUnavailable,
+    InvalidTarget,
+    ChatTooLong,
+}
+
+impl ErrorCode {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::InvalidMessage => "the message couldn't be read",
+            Self::UnknownMessage => "only text messages are supported",
+            Self::InvalidDirective => "the message isn't a valid directive",
+            Self::UnexpectedDirective => "the directive isn't available at the moment",
+            Self::InvalidNickname => "the nickname is empty, too long or has control characters",
+            Self::UnsupportedVersion => "the server supports none of the versions of the protocol of the client",
+            Self::ResumeFailed => "there is no session to resume with this token",
+            Self::InvalidSecret => "the secret doesn't fit the format of the lobby",
+            Self::NotHost => "only the host of the lobby can do this",
+            Self::NotYourTurn => "it isn't your turn to guess",
+            Self::NotEnoughPlayers => "a game needs at least two players",
+            Self::SecretsNotSet => "every player must set a secret first",
+            Self::UnevenTeams => "the teams must be of the same size",
+            Self::LobbyNotFound => "there is no lobby with this id",
+            Self::LobbyFull => "the lobby is full",
+            Self::GameRunning => "a game is running in the lobby",
+            Self::TooManyMembers => "the lobby has more members than the players it allows",
+            Self::InvalidSettings => "the settings are invalid",
+            Self::BotUnavailable => "a bot can only join a lobby for two without a guest",
+            Self::HintUnavailable => "no hint is available at the moment",
+            Self::InvalidTarget => "the target must be another player who is still in the game",
+            Self::ChatTooLong => "the chat message is too long",
+        }
+    }
 }
 
 #[non_exhaustive]
 #[derive(Debug, Serialize)]
 #[serde(tag = "type")]
 pub enum Notification<'a> {
-    LobbyCreate { lobby_id: LobbyId },
-    LobbyJoin { lobby_id: LobbyId },
+    /// Sent when a client connects. The token resumes the session if the
+    /// connection drops.
+    Session { token: &'a str },
+    SessionResume,
+    /// Accepts the `Hello` of a client, with the version of the protocol
+    /// chosen for the client.
+    Welcome {
+        nickname: &'a str,
+        version: u32,
+        capabilities: Capabilities,
+    },
+    LobbyCreate {
+        lobby_id: LobbyId,
+        #[serde(flatten)]
+        settings: Settings,
+    },
+    /// Sent to a new member of a lobby, with the nicknames of the members
+    /// who are already in the lobby.
+    LobbyJoin {
+        lobby_id: LobbyId,
+        #[serde(flatten)]
+        settings: Settings,
+        members: Vec<Option<&'a str>>,
+    },
+    SettingsChanged {
+        #[serde(flatten)]
+        settings: Settings,
+    },
     SecretSet { secret: &'a Secret },
-    GuestJoin,
+    GuestJoin { nickname: Option<&'a str> },
     OpponentLeave,
-    GameStart,
-    NextTurn,
-    GuessScore { secret: &'a Secret, correct: u8, wrong: u8 },
+    GameStart {
+        series: Standing,
+        opponent: Option<&'a str>,
+    },
+    NextTurn {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        clocks: Option<Clocks>,
+        /// The number of turns in a row the player has missed, as a warning.
+        missed_turns: u32,
+    },
+    GuessScore {
+        secret: &'a Secret,
+        #[serde(flatten)]
+        feedback: Feedback,
+    },
+    InconsistentGuess { secret: &'a Secret, violates: &'a Record },
+    /// The guess is accepted in simultaneous mode, and it will be scored
+    /// when the round ends.
+    GuessLocked { secret: &'a Secret },
+    OpponentLocked,
+    Hint {
+        #[serde(flatten)]
+        clue: Clue,
+    },
+    OpponentHint { kind: HintKind },
+    History {
+        player: &'a [Record],
+        opponent: &'a [Record],
+    },
+    DrawOffer,
+    DrawDecline,
+    GameEnd {
+        outcome: Outcome,
+        ending: Ending,
+        duration_ms: u64,
+        player: Summary<'a>,
+        opponent: Summary<'a>,
+        series: Standing,
+    },
+    RematchOffer,
+    RematchAccept,
+    /// The nicknames of the players are in the order of their seats.
+    FreeForAllStart {
+        seat: usize,
+        players: usize,
+        nicknames: Vec<Option<&'a str>>,
+    },
+    TargetScore {
+        target: usize,
+        secret: &'a Secret,
+        #[serde(flatten)]
+        feedback: Feedback,
+    },
+    /// A player is out of a free-for-all game. If the secret of the player
+    /// is found, `by` is the seat of the player who has found it.
+    Eliminated {
+        seat: usize,
+        by: Option<usize>,
+        secret: &'a Secret,
+    },
+    FreeForAllEnd {
+        winner: Option<usize>,
+        duration_ms: u64,
+        secrets: Vec<&'a Secret>,
+    },
+    TeamJoin { team: usize },
+    /// A message from a teammate, who is identified by their seat.
+    TeamChat { seat: usize, message: &'a str },
+    TeamGameStart {
+        seat: usize,
+        team: usize,
+        nicknames: Vec<Option<&'a str>>,
+    },
+    /// A guess of a teammate, sent to the rest of the team.
+    TeammateGuess {
+        seat: usize,
+        secret: &'a Secret,
+        #[serde(flatten)]
+        feedback: Feedback,
+    },
+    SpectateStart {
+        lobby_id: LobbyId,
+        #[serde(flatten)]
+        settings: Settings,
+    },
+    Spectate {
+        #[serde(flatten)]
+        event: Event,
+    },
+    SpectateEnd,
+    TeamGameEnd {
+        winner: Option<usize>,
+        ending: Ending,
+        duration_ms: u64,
+        secrets: [&'a Secret; 2],
+    },
+    SoloStart {
+        #[serde(flatten)]
+        settings: Settings,
+    },
+    SoloWin { guesses: u32, elapsed_ms: u64 },
+    /// A directive of the client is rejected. The directive is the type of
+    /// the rejected directive, if it could be read.
+    Error {
+        code: ErrorCode,
+        message: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        directive: Option<&'static str>,
+    },
+    /// The ends of a game in the first version of the protocol.
     Win,
-    Lose
+    Lose,
+    LobbyState {
+        lobby_id: LobbyId,
+        #[serde(flatten)]
+        settings: Settings,
+        members: usize,
+        host: bool,
+        secret: Option<&'a Secret>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        team: Option<usize>,
+    },
+    /// The state of a duel, from the point of view of the player who asks.
+    GameState {
+        secret: &'a Secret,
+        can_guess: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        clocks: Option<Clocks>,
+        missed_turns: u32,
+        draw_offer: bool,
+        player: &'a [Record],
+        opponent: &'a [Record],
+        series: Standing,
+    },
+    FreeForAllState {
+        seat: usize,
+        turn: usize,
+        secret: &'a Secret,
+        eliminated: Vec<usize>,
+    },
+    TeamGameState {
+        seat: usize,
+        team: usize,
+        turn: usize,
+        secret: &'a Secret,
+    },
+    SoloState {
+        #[serde(flatten)]
+        settings: Settings,
+        guesses: u32,
+        history: &'a [Record],
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{from_str, Value};
+
+    #[test]
+    fn names_directives_by_their_type() {
+        let texts = [
+            r#"{ "type": "Leave" }"#,
+            r#"{ "type": "JoinLobby", "lobby_id": 3 }"#,
+            r#"{ "type": "Guess", "secret": "123", "target": 1 }"#,
+            r#"{ "type": "Hello", "nickname": "alice", "versions": [2] }"#,
+            r#"{ "type": "CreateLobby", "players": 4 }"#,
+            r#"{ "type": "UpdateSettings", "turn_secs": 60 }"#,
+        ];
+
+        for text in texts {
+            let directive: Directive = from_str(text).unwrap();
+            let value: Value = from_str(text).unwrap();
+
+            assert_eq!(directive.name(), value["type"]);
+        }
+    }
 }
@@ -0,0 +1,199 @@
+use crate::{
+    game::Outcome,
+    scoring::Variant,
+    settings::Mode,
+    Notification, Settings,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+/// The versions of the protocol the server speaks, the oldest first.
+///
+/// 1. The first protocol, where a game ends with a `Win` or a `Lose`, and the
+///    secrets are sent as numbers.
+/// 2. A game ends with a `GameEnd`, which has the summaries of the players.
+///    The secrets are sent as strings, which keep their leading zeros.
+pub const VERSIONS: [u32; 2] = [1, 2];
+
+/// The version of the clients that don't declare the versions they support.
+pub const LEGACY_VERSION: u32 = 1;
+
+/// Chooses the latest version that both the server and the client support.
+/// A client that declares no versions speaks the legacy version.
+pub fn negotiate(versions: &[u32]) -> Option<u32> {
+    if versions.is_empty() {
+        return Some(LEGACY_VERSION);
+    }
+
+    VERSIONS
+        .into_iter()
+        .rev()
+        .find(|version| versions.contains(version))
+}
+
+/// The optional features of the server, sent to the clients in the handshake.
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    pub variants: [Variant; 4],
+    pub modes: [Mode; 2],
+    pub max_players: u32,
+    pub spectate: bool,
+    pub chat: bool,
+    pub resume: bool,
+    pub bots: bool,
+    pub solo: bool,
+}
+
+impl Capabilities {
+    pub fn new() -> Self {
+        Self {
+            variants: Variant::ALL,
+            modes: [Mode::Alternating, Mode::Simultaneous],
+            max_players: Settings::MAX_PLAYERS,
+            spectate: true,
+            chat: true,
+            resume: true,
+            bots: true,
+            solo: true,
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Adapts the notification to an older version of the protocol. The
+/// notifications that have no counterpart in the version are sent as they
+/// are, and the clients are expected to ignore what they don't know.
+pub fn shim(version: u32, notification: Notification<'_>) -> Notification<'_> {
+    use Notification::*;
+
+    match (version, notification) {
+        (1, GameEnd { outcome: Outcome::Win, .. }) => Win,
+        (1, GameEnd { outcome: Outcome::Lose, .. }) => Lose,
+        (_, notification) => notification,
+    }
+}
+
+/// Converts the notification to json in the version of the protocol.
+pub fn to_json(version: u32, notification: Notification<'_>) -> String {
+    let notification = shim(version, notification);
+
+    if version == 1 {
+        let mut json = serde_json::to_value(notification).expect("Couldn't parse notification to json");
+        secrets_as_numbers(&mut json);
+
+        json.to_string()
+    } else {
+        serde_json::to_string(&notification).expect("Couldn't parse notification to json")
+    }
+}
+
+/// The fields of the notifications that hold a secret, or a list of secrets.
+const SECRET_FIELDS: [&str; 3] = ["secret", "guess", "secrets"];
+
+/// Sends the secrets as the numbers their digits spell, like the first
+/// version of the protocol does. The leading zeros are implied by the length
+/// of the format.
+fn secrets_as_numbers(json: &mut Value) {
+    match json {
+        Value::Object(fields) => {
+            for (name, value) in fields.iter_mut() {
+                if SECRET_FIELDS.contains(&name.as_str()) {
+                    to_number(value);
+                } else {
+                    secrets_as_numbers(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(secrets_as_numbers),
+        _ => {}
+    }
+}
+
+fn to_number(secret: &mut Value) {
+    match secret {
+        Value::String(digits) => {
+            if let Ok(number) = digits.parse::<u64>() {
+                *secret = number.into();
+            }
+        }
+        Value::Array(secrets) => secrets.iter_mut().for_each(to_number),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        game::{Ending, Record, Summary},
+        scoring::Feedback,
+        series::Series,
+        Secret,
+    };
+    use serde_json::json;
+    use Notification::*;
+
+    #[test]
+    fn negotiates_the_latest_common_version() {
+        assert_eq!(negotiate(&[]), Some(LEGACY_VERSION));
+        assert_eq!(negotiate(&[1, 2, 3]), Some(2));
+        assert_eq!(negotiate(&[1]), Some(1));
+        assert_eq!(negotiate(&[3]), None);
+    }
+
+    #[test]
+    fn shims_the_end_of_a_game() {
+        let secret = Secret::parse("123").unwrap();
+        let game_end = |outcome| GameEnd {
+            outcome,
+            ending: Ending::Solved,
+            duration_ms: 0,
+            player: Summary { secret: &secret, guesses: 0, history: &[] },
+            opponent: Summary { secret: &secret, guesses: 0, history: &[] },
+            series: Series::new(1).standing(true),
+        };
+
+        assert!(matches!(shim(1, game_end(Outcome::Win)), Win));
+        assert!(matches!(shim(1, game_end(Outcome::Lose)), Lose));
+        assert!(matches!(shim(1, game_end(Outcome::Draw)), GameEnd { .. }));
+        assert!(matches!(shim(2, game_end(Outcome::Win)), GameEnd { .. }));
+    }
+
+    #[test]
+    fn sends_secrets_as_numbers_in_the_first_version() {
+        let secret = Secret::parse("012").unwrap();
+        let guess = Secret::parse("345").unwrap();
+        let record = Record {
+            guess: guess.clone(),
+            feedback: Feedback::BullsAndCows { correct: 0, wrong: 1 },
+            elapsed_ms: 0,
+        };
+        let history = [record];
+
+        let json = |version, notification| -> Value {
+            serde_json::from_str(&to_json(version, notification)).unwrap()
+        };
+
+        let secret_set = || SecretSet { secret: &secret };
+        assert_eq!(json(1, secret_set())["secret"], 12);
+        assert_eq!(json(2, secret_set())["secret"], "012");
+
+        let revealed = || TeamGameEnd {
+            winner: None,
+            ending: Ending::Solved,
+            duration_ms: 0,
+            secrets: [&secret, &guess],
+        };
+        assert_eq!(json(1, revealed())["secrets"], json!([12, 345]));
+        assert_eq!(json(2, revealed())["secrets"], json!(["012", "345"]));
+
+        let history = json(1, History { player: &history, opponent: &[] });
+        assert_eq!(history["player"][0]["guess"], 345);
+        assert_eq!(history["player"][0]["correct"], 0);
+    }
+}
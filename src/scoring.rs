@@ -0,0 +1,179 @@
+use crate::Secret;
+use serde::{Deserialize, Serialize};
+
+/// A way of telling a player how close a guess is to the secret.
+pub trait ScoringRule: Send + Sync {
+    fn score(&self, secret: &Secret, guess: &Secret) -> Feedback;
+}
+
+/// The feedback given for a guess, its shape depends on the scoring rule.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "feedback")]
+pub enum Feedback {
+    BullsAndCows { correct: u8, wrong: u8 },
+    Matches { matches: u8 },
+    Positions { hints: Vec<Hint> },
+    Pegs { black: u8, white: u8 },
+}
+
+/// The hint given for a single digit of a guess.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Hint {
+    /// The digit is in the correct position.
+    Correct,
+    /// The digit appears in the secret, but in a different position.
+    Misplaced,
+    /// The digit doesn't appear in the secret (any more).
+    Absent,
+}
+
+/// The classic bulls and cows, the number of the digits in the correct
+/// position and the number of the digits in a wrong position.
+pub struct Classic;
+
+impl ScoringRule for Classic {
+    fn score(&self, secret: &Secret, guess: &Secret) -> Feedback {
+        let (correct, wrong) = secret.score(guess);
+        Feedback::BullsAndCows { correct, wrong }
+    }
+}
+
+/// Only the total number of the matching digits, regardless of their
+/// positions.
+pub struct Matches;
+
+impl ScoringRule for Matches {
+    fn score(&self, secret: &Secret, guess: &Secret) -> Feedback {
+        let (correct, wrong) = secret.score(guess);
+        Feedback::Matches {
+            matches: correct + wrong,
+        }
+    }
+}
+
+/// A hint for every digit of the guess.
+///
+/// Like the score, the hints are counted as multisets. If a digit is repeated
+/// in the guess more than it appears in the secret, the extra occurrences are
+/// `Absent`.
+pub struct Positions;
+
+impl ScoringRule for Positions {
+    fn score(&self, secret: &Secret, guess: &Secret) -> Feedback {
+        let secret = secret.digits();
+        let guess = guess.digits();
+
+        let mut hints = vec![Hint::Absent; guess.len()];
+        let mut remaining = [0u8; 10];
+
+        for (i, (&secret, &guess)) in secret.iter().zip(guess.iter()).enumerate() {
+            if secret == guess {
+                hints[i] = Hint::Correct;
+            } else {
+                remaining[secret as usize] += 1;
+            }
+        }
+
+        for (hint, &digit) in hints.iter_mut().zip(guess.iter()) {
+            if *hint == Hint::Absent && remaining[digit as usize] > 0 {
+                remaining[digit as usize] -= 1;
+                *hint = Hint::Misplaced;
+            }
+        }
+
+        Feedback::Positions { hints }
+    }
+}
+
+/// Mastermind, where the digits stand for the colors of a small palette, and
+/// the colors can repeat. A black peg for every color in the correct position,
+/// and a white peg for every other matching color.
+///
+/// The palette and the repeats are required by `Settings::validate`.
+pub struct Mastermind;
+
+impl ScoringRule for Mastermind {
+    fn score(&self, secret: &Secret, guess: &Secret) -> Feedback {
+        let (black, white) = secret.score(guess);
+        Feedback::Pegs { black, white }
+    }
+}
+
+/// The scoring rule of a lobby, chosen by the host.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Variant {
+    #[default]
+    Classic,
+    Matches,
+    Positions,
+    Mastermind,
+}
+
+impl Variant {
+    pub const ALL: [Self; 4] = [Self::Classic, Self::Matches, Self::Positions, Self::Mastermind];
+
+    pub fn rule(&self) -> &'static dyn ScoringRule {
+        match self {
+            Self::Classic => &Classic,
+            Self::Matches => &Matches,
+            Self::Positions => &Positions,
+            Self::Mastermind => &Mastermind,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::secret::Format;
+
+    fn score(variant: Variant, secret: &str, guess: &str) -> Feedback {
+        let format = Format::with_colors(4, true, 6).unwrap();
+        let secret = Secret::parse_with(secret, &format).unwrap();
+        let guess = Secret::parse_with(guess, &format).unwrap();
+
+        variant.rule().score(&secret, &guess)
+    }
+
+    #[test]
+    fn scores_classic() {
+        assert_eq!(
+            score(Variant::Classic, "1234", "1325"),
+            Feedback::BullsAndCows { correct: 1, wrong: 2 }
+        );
+    }
+
+    #[test]
+    fn scores_matches() {
+        assert_eq!(
+            score(Variant::Matches, "1234", "1325"),
+            Feedback::Matches { matches: 3 }
+        );
+    }
+
+    #[test]
+    fn scores_positions() {
+        use Hint::*;
+
+        assert_eq!(
+            score(Variant::Positions, "1234", "1325"),
+            Feedback::Positions {
+                hints: vec![Correct, Misplaced, Misplaced, Absent]
+            }
+        );
+        assert_eq!(
+            score(Variant::Positions, "1123", "3111"),
+            Feedback::Positions {
+                hints: vec![Misplaced, Correct, Misplaced, Absent]
+            }
+        );
+    }
+
+    #[test]
+    fn scores_pegs() {
+        assert_eq!(
+            score(Variant::Mastermind, "1122", "1212"),
+            Feedback::Pegs { black: 2, white: 2 }
+        );
+    }
+}
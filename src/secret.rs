@@ -1,12 +1,199 @@
+use rand::{seq::SliceRandom, Rng};
 use serde::{
     de::{Deserialize, Deserializer, Error, Unexpected, Visitor},
-    Serialize,
+    Serialize, Serializer,
 };
-use std::fmt::{Formatter, Result as FormatResult};
-use std::num::NonZeroU16;
+use std::fmt::{Display, Formatter, Result as FormatResult};
 
-#[derive(Debug, PartialEq, Serialize)]
-pub struct Secret(NonZeroU16);
+/// A sequence of digits chosen by a player, validated against a `Format`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Secret(Vec<u8>);
+
+/// The constraints a secret must satisfy in order to be accepted.
+///
+/// A format is chosen by the host of a lobby, and it is validated when it is
+/// deserialized, so a `Format` always describes a playable secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, serde::Deserialize)]
+#[serde(try_from = "UncheckedFormat")]
+pub struct Format {
+    /// The number of digits of a secret.
+    length: usize,
+    /// Whether a digit can appear more than once in a secret.
+    repeats: bool,
+    /// The number of the digits a secret can use, counting from 0. In
+    /// Mastermind the digits stand for the colors of the palette.
+    colors: u8,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            length: 3,
+            repeats: false,
+            colors: 10,
+        }
+    }
+}
+
+impl Format {
+    pub const MIN_LENGTH: usize = 3;
+    pub const MAX_LENGTH: usize = 10;
+    pub const MIN_COLORS: u8 = 2;
+    pub const MAX_COLORS: u8 = 10;
+
+    pub fn new(length: usize, repeats: bool) -> Result<Self, FormatError> {
+        Self::with_colors(length, repeats, Self::MAX_COLORS)
+    }
+
+    /// Creates a format whose secrets use only the first `colors` digits.
+    pub fn with_colors(length: usize, repeats: bool, colors: u8) -> Result<Self, FormatError> {
+        if !(Self::MIN_LENGTH..=Self::MAX_LENGTH).contains(&length) {
+            return Err(FormatError::Length { found: length });
+        }
+
+        if !(Self::MIN_COLORS..=Self::MAX_COLORS).contains(&colors) {
+            return Err(FormatError::Colors { found: colors });
+        }
+
+        // Without repeats, every digit of a secret needs a color of its own.
+        if !repeats && length > colors as usize {
+            return Err(FormatError::Palette { length, colors });
+        }
+
+        Ok(Self {
+            length,
+            repeats,
+            colors,
+        })
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn repeats(&self) -> bool {
+        self.repeats
+    }
+
+    pub fn colors(&self) -> u8 {
+        self.colors
+    }
+}
+
+/// The reason of a format being rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    Length { found: usize },
+    Colors { found: u8 },
+    Palette { length: usize, colors: u8 },
+}
+
+impl Display for FormatError {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match self {
+            Self::Length { found } => write!(
+                formatter,
+                "a secret must have {} to {} digits, not {}",
+                Format::MIN_LENGTH,
+                Format::MAX_LENGTH,
+                found
+            ),
+            Self::Colors { found } => write!(
+                formatter,
+                "a secret must use {} to {} colors, not {}",
+                Format::MIN_COLORS,
+                Format::MAX_COLORS,
+                found
+            ),
+            Self::Palette { length, colors } => write!(
+                formatter,
+                "{} unique digits don't fit in {} colors",
+                length, colors
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// The deserialized form of a `Format` before it is validated, the missing
+/// fields are filled with the defaults.
+#[derive(serde::Deserialize)]
+#[serde(default)]
+struct UncheckedFormat {
+    length: usize,
+    repeats: bool,
+    colors: u8,
+}
+
+impl Default for UncheckedFormat {
+    fn default() -> Self {
+        let Format {
+            length,
+            repeats,
+            colors,
+        } = Format::default();
+
+        Self {
+            length,
+            repeats,
+            colors,
+        }
+    }
+}
+
+impl TryFrom<UncheckedFormat> for Format {
+    type Error = FormatError;
+
+    fn try_from(format: UncheckedFormat) -> Result<Self, Self::Error> {
+        Format::with_colors(format.length, format.repeats, format.colors)
+    }
+}
+
+/// The reason of a secret being rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason")]
+pub enum SecretError {
+    Length { expected: usize, found: usize },
+    RepeatedDigit { digit: u8 },
+    NonDigit { character: char },
+    Color { digit: u8, colors: u8 },
+}
+
+impl Display for SecretError {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match self {
+            Self::Length { expected, found } => {
+                write!(formatter, "expected {} digits, found {}", expected, found)
+            }
+            Self::RepeatedDigit { digit } => write!(formatter, "the digit {} is repeated", digit),
+            Self::NonDigit { character } => write!(formatter, "{:?} is not a digit", character),
+            Self::Color { digit, colors } => {
+                write!(formatter, "the digit {} is not one of the {} colors", digit, colors)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+/// The raw form of a secret sent by a client, either as a JSON number or a
+/// JSON string. It is validated into a `Secret` with the format of the lobby.
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+pub enum SecretInput {
+    Number(u64),
+    Text(String),
+}
+
+impl SecretInput {
+    pub fn parse(&self, format: &Format) -> Result<Secret, SecretError> {
+        match self {
+            Self::Number(n) => Secret::from_number(*n, format),
+            Self::Text(text) => Secret::parse_with(text, format),
+        }
+    }
+}
 
 struct SecretVisitor;
 
@@ -14,32 +201,16 @@ impl<'a> Visitor<'a> for SecretVisitor {
     type Value = Secret;
 
     fn expecting(&self, formatter: &mut Formatter) -> FormatResult {
-        formatter.write_str("a sequence of three unique digits")
+        formatter.write_str("a secret in the default format, as a number or a string of digits")
     }
 
     fn visit_u64<E: Error>(self, n: u64) -> Result<Self::Value, E> {
-        if 988 > n && n > 10 {
-            let units = n % 10;
-            let tens = (n / 10) % 10;
-            let hundreds = (n / 100) % 10;
-
-            // The digits of the number must be unique.
-            if units != tens && units != hundreds && hundreds != tens {
-                unsafe {
-                    // SAFETY: The number is guaranteed to be less than u16::MAX.
-                    let n: u16 = n.try_into().unwrap_unchecked();
-
-                    // SAFETY: The number is guaranteed to be greater than zero in
-                    // this scope, because the first if conditon eliminated that
-                    // possibility earlier.
-                    Ok(Secret(NonZeroU16::new_unchecked(n)))
-                }
-            } else {
-                Err(Error::invalid_value(Unexpected::Unsigned(n), &self))
-            }
-        } else {
-            Err(Error::invalid_value(Unexpected::Unsigned(n), &self))
-        }
+        Secret::from_number(n, &Format::default())
+            .or(Err(Error::invalid_value(Unexpected::Unsigned(n), &self)))
+    }
+
+    fn visit_str<E: Error>(self, text: &str) -> Result<Self::Value, E> {
+        Secret::parse(text).or(Err(Error::invalid_value(Unexpected::Str(text), &self)))
     }
 }
 
@@ -48,38 +219,155 @@ impl<'de> Deserialize<'de> for Secret {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_u64(SecretVisitor)
+        deserializer.deserialize_any(SecretVisitor)
+    }
+}
+
+/// A secret is sent as a string of its digits, so that the leading zeros are
+/// kept. The first version of the protocol is shimmed to send numbers.
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        self.0
+            .iter()
+            .try_for_each(|digit| write!(formatter, "{}", digit))
     }
 }
 
 impl Secret {
-    pub fn parse<T: AsRef<str>>(_: T) -> Option<Self> {
-        None
+    /// Parses a secret in the default format.
+    pub fn parse<T: AsRef<str>>(text: T) -> Result<Self, SecretError> {
+        Self::parse_with(text, &Format::default())
     }
 
-    pub fn score(&self, guess: &Secret) -> (u8, u8) {
-        let secret = self.0.get();
-        let guess = guess.0.get();
+    /// Parses a secret, surrounding whitespace is ignored. Like numbers, short
+    /// secrets are padded with leading zeros, so "12" is read as "012".
+    pub fn parse_with<T: AsRef<str>>(text: T, format: &Format) -> Result<Self, SecretError> {
+        let digits = text
+            .as_ref()
+            .trim()
+            .chars()
+            .map(|character| match character.to_digit(10) {
+                Some(digit) => Ok(digit as u8),
+                None => Err(SecretError::NonDigit { character }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::validate(digits, format)
+    }
 
-        let secret = [(secret / 100) % 10, (secret / 10) % 10, secret % 10];
-        let guess = [(guess / 100) % 10, (guess / 10) % 10, guess % 10];
+    /// Reads the digits of a number, padding it with leading zeros up to the
+    /// length of the format. So, 12 is read as "012" in the default format.
+    pub fn from_number(mut n: u64, format: &Format) -> Result<Self, SecretError> {
+        let mut digits = vec![(n % 10) as u8];
 
+        while n >= 10 {
+            n /= 10;
+            digits.push((n % 10) as u8);
+        }
+
+        digits.reverse();
+        Self::validate(digits, format)
+    }
+
+    /// Validates the digits of a secret, like a parsed secret.
+    pub fn from_digits(digits: Vec<u8>, format: &Format) -> Result<Self, SecretError> {
+        Self::validate(digits, format)
+    }
+
+    fn validate(mut digits: Vec<u8>, format: &Format) -> Result<Self, SecretError> {
+        if digits.is_empty() || digits.len() > format.length {
+            return Err(SecretError::Length {
+                expected: format.length,
+                found: digits.len(),
+            });
+        }
+
+        // Pad the missing digits with leading zeros.
+        let padding = format.length - digits.len();
+        digits.splice(0..0, std::iter::repeat_n(0, padding));
+
+        if let Some(&digit) = digits.iter().find(|&&digit| digit >= format.colors) {
+            return Err(SecretError::Color {
+                digit,
+                colors: format.colors,
+            });
+        }
+
+        // Unless the format allows repeats, the digits of the secret must be
+        // unique.
+        if !format.repeats {
+            let mut seen = [false; 10];
+
+            for &digit in &digits {
+                if std::mem::replace(&mut seen[digit as usize], true) {
+                    return Err(SecretError::RepeatedDigit { digit });
+                }
+            }
+        }
+
+        Ok(Self(digits))
+    }
+
+    /// Picks a random secret that is valid in the format.
+    pub fn random<R: Rng + ?Sized>(format: &Format, rng: &mut R) -> Self {
+        if format.repeats {
+            Self((0..format.length).map(|_| rng.gen_range(0..format.colors)).collect())
+        } else {
+            let mut digits: Vec<u8> = (0..format.colors).collect();
+            let (digits, _) = digits.partial_shuffle(rng, format.length);
+            Self(digits.to_vec())
+        }
+    }
+
+    pub fn digits(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The number the digits spell, so "012" is 12.
+    pub fn to_number(&self) -> u64 {
+        self.0.iter().fold(0, |n, &digit| n * 10 + u64::from(digit))
+    }
+
+    /// Returns the number of the digits of the guess in the correct position,
+    /// and the number of the remaining digits that appear in the secret in a
+    /// different position.
+    ///
+    /// The digits are counted as multisets, so a repeated digit of the guess
+    /// is counted at most as many times as it appears in the secret.
+    pub fn score(&self, guess: &Secret) -> (u8, u8) {
         let mut correct_position = 0;
-        let mut wrong_position = 0;
 
-        for i in 0..3 {
-            if secret[i] == guess[i] {
+        // Occurrences of each digit that are not in their correct position.
+        let mut secret_counts = [0u8; 10];
+        let mut guess_counts = [0u8; 10];
+
+        for (&secret, &guess) in self.0.iter().zip(guess.0.iter()) {
+            if secret == guess {
                 correct_position += 1;
-            } else if secret[i % 3] == guess[(i + 1) % 3] || secret[i % 3] == guess[(i + 2) % 3] {
-                wrong_position += 1;
+            } else {
+                secret_counts[secret as usize] += 1;
+                guess_counts[guess as usize] += 1;
             }
         }
 
+        let wrong_position = secret_counts
+            .iter()
+            .zip(guess_counts.iter())
+            .map(|(secret, guess)| secret.min(guess))
+            .sum();
+
         (correct_position, wrong_position)
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::redundant_pattern_matching)]
 mod test {
     use super::*;
     use serde_json::{from_value, json};
@@ -121,6 +409,129 @@ mod test {
         accepts!(123, 152, 921, 756, 987, 12, 19, 536, 671);
     }
 
+    #[test]
+    fn accepts_strings() {
+        accepts!("012", "12", " 987 ", "123");
+        rejects!("", "1234", "1a3", "-12", "1 2", "101");
+    }
+
+    #[test]
+    fn parses_with_typed_errors() {
+        assert_eq!(Secret::parse(" 012 ").unwrap().to_string(), "012");
+        assert_eq!(Secret::parse("12").unwrap().to_string(), "012");
+        assert_eq!(
+            Secret::parse("1234"),
+            Err(SecretError::Length { expected: 3, found: 4 })
+        );
+        assert_eq!(
+            Secret::parse("919"),
+            Err(SecretError::RepeatedDigit { digit: 9 })
+        );
+        assert_eq!(
+            Secret::parse("1x3"),
+            Err(SecretError::NonDigit { character: 'x' })
+        );
+    }
+
+    #[test]
+    fn serializes_as_a_string() {
+        let secret = Secret::parse("012").unwrap();
+
+        assert_eq!(json!(secret), json!("012"));
+    }
+
+    #[test]
+    fn parses_with_format_length() {
+        let format = Format::new(4, false).unwrap();
+
+        assert_eq!(Secret::parse_with("0123", &format).unwrap().to_string(), "0123");
+        assert_eq!(Secret::from_number(987, &format).unwrap().to_string(), "0987");
+        assert_eq!(
+            Secret::parse_with("12345", &format),
+            Err(SecretError::Length { expected: 4, found: 5 })
+        );
+    }
+
+    #[test]
+    fn validates_formats() {
+        assert!(Format::new(2, false).is_err());
+        assert!(Format::new(11, true).is_err());
+        assert!((3..=10).all(|length| Format::new(length, false).is_ok()));
+
+        assert_eq!(from_value::<Format>(json!({})).unwrap(), Format::default());
+        assert_eq!(from_value::<Format>(json!({ "length": 5 })).unwrap().length(), 5);
+        assert!(from_value::<Format>(json!({ "length": 1 })).is_err());
+        assert!(from_value::<Format>(json!({ "repeats": true })).unwrap().repeats());
+    }
+
+    #[test]
+    fn limits_the_colors() {
+        assert!(Format::with_colors(4, true, 1).is_err());
+        assert!(Format::with_colors(4, true, 11).is_err());
+        assert!(Format::with_colors(4, false, 3).is_err());
+
+        let format = Format::with_colors(4, true, 6).unwrap();
+
+        assert_eq!(Secret::parse_with("5501", &format).unwrap().to_string(), "5501");
+        assert_eq!(
+            Secret::parse_with("1261", &format),
+            Err(SecretError::Color { digit: 6, colors: 6 })
+        );
+        assert!((0..100).all(|_| {
+            let secret = Secret::random(&format, &mut rand::thread_rng());
+            secret.digits().iter().all(|&digit| digit < 6)
+        }));
+    }
+
+    #[test]
+    fn accepts_repeats_if_allowed() {
+        let format = Format::new(3, true).unwrap();
+
+        assert_eq!(Secret::parse_with("112", &format).unwrap().to_string(), "112");
+        assert_eq!(Secret::from_number(0, &format).unwrap().to_string(), "000");
+        assert_eq!(Secret::from_number(909, &format).unwrap().to_string(), "909");
+    }
+
+    #[test]
+    fn scores_repeated_digits_as_multisets() {
+        let format = Format::new(4, true).unwrap();
+        let score = |secret: &str, guess: &str| {
+            let secret = Secret::parse_with(secret, &format).unwrap();
+            let guess = Secret::parse_with(guess, &format).unwrap();
+            secret.score(&guess)
+        };
+
+        assert_eq!(score("1122", "2211"), (0, 4));
+        assert_eq!(score("1234", "1111"), (1, 0));
+        assert_eq!(score("1123", "3111"), (1, 2));
+        assert_eq!(score("9090", "0909"), (0, 4));
+        assert_eq!(score("1200", "0011"), (0, 3));
+    }
+
+    #[test]
+    fn scores_longer_guesses() {
+        let format = Format::new(5, false).unwrap();
+        let secret = Secret::parse_with("01234", &format).unwrap();
+        let guess = Secret::parse_with("43210", &format).unwrap();
+
+        assert_eq!(secret.score(&guess), (1, 4));
+        assert_eq!(secret.score(&secret), (5, 0));
+    }
+
+    #[test]
+    fn picks_valid_random_secrets() {
+        let mut rng = rand::thread_rng();
+
+        for length in Format::MIN_LENGTH..=Format::MAX_LENGTH {
+            for repeats in [false, true] {
+                let format = Format::new(length, repeats).unwrap();
+                let secret = Secret::random(&format, &mut rng);
+
+                assert_eq!(Secret::parse_with(secret.to_string(), &format), Ok(secret));
+            }
+        }
+    }
+
     #[test]
     fn scores_guesses() {
         scores! {
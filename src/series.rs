@@ -0,0 +1,117 @@
+use crate::game::Outcome;
+use serde::Serialize;
+
+/// A match series between the host and the guest of a lobby. The players
+/// keep playing games until one of them wins the majority of the games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Series {
+    best_of: u32,
+    games: u32,
+    draws: u32,
+    host_wins: u32,
+    guest_wins: u32,
+}
+
+/// The score of a series from the point of view of a player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Standing {
+    pub best_of: u32,
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// Whether the series is decided. The members of the lobby may start a
+    /// new series with a rematch.
+    pub over: bool,
+}
+
+impl Series {
+    pub fn new(best_of: u32) -> Self {
+        Self {
+            best_of,
+            games: 0,
+            draws: 0,
+            host_wins: 0,
+            guest_wins: 0,
+        }
+    }
+
+    /// Returns true if the host moves first in the next game. The host moves
+    /// first in the first game, and then the players take turns to move first.
+    pub fn host_first(&self) -> bool {
+        self.games.is_multiple_of(2)
+    }
+
+    /// Returns true if a player has won the majority of the games, or all
+    /// the games are played.
+    pub fn is_over(&self) -> bool {
+        let majority = self.best_of / 2 + 1;
+
+        self.host_wins >= majority || self.guest_wins >= majority || self.games >= self.best_of
+    }
+
+    /// Records the outcome of a game for the host.
+    pub fn record(&mut self, outcome: Outcome) {
+        self.games += 1;
+
+        match outcome {
+            Outcome::Win => self.host_wins += 1,
+            Outcome::Lose => self.guest_wins += 1,
+            Outcome::Draw => self.draws += 1,
+        }
+    }
+
+    pub fn standing(&self, of_host: bool) -> Standing {
+        let (wins, losses) = if of_host {
+            (self.host_wins, self.guest_wins)
+        } else {
+            (self.guest_wins, self.host_wins)
+        };
+
+        Standing {
+            best_of: self.best_of,
+            games: self.games,
+            wins,
+            losses,
+            draws: self.draws,
+            over: self.is_over(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ends_with_the_majority() {
+        let mut series = Series::new(3);
+        assert!(series.host_first());
+
+        series.record(Outcome::Win);
+        assert!(!series.is_over());
+        assert!(!series.host_first());
+
+        series.record(Outcome::Lose);
+        assert!(!series.is_over());
+        assert!(series.host_first());
+
+        series.record(Outcome::Win);
+        assert!(series.is_over());
+
+        let standing = series.standing(false);
+        assert_eq!((standing.wins, standing.losses), (1, 2));
+    }
+
+    #[test]
+    fn ends_when_all_games_are_played() {
+        let mut series = Series::new(3);
+
+        series.record(Outcome::Draw);
+        series.record(Outcome::Draw);
+        assert!(!series.is_over());
+
+        series.record(Outcome::Win);
+        assert!(series.is_over());
+    }
+}
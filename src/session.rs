@@ -0,0 +1,132 @@
+use crate::client::Client;
+use rand::Rng;
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, RwLock},
+};
+use tokio::{
+    sync::mpsc::{channel, error::TrySendError, Receiver, Sender},
+    time::{timeout_at, Duration, Instant},
+};
+
+/// How long the session of a dropped connection is held for a new connection
+/// to resume it.
+pub const GRACE_SECS: u64 = 30;
+
+pub type Token = String;
+type SessionIndex = Arc<RwLock<HashMap<Token, Sender<Client>>>>;
+
+static SESSIONS: LazyLock<SessionIndex> = LazyLock::new(|| Arc::new(RwLock::new(HashMap::new())));
+
+/// The session of a connected client. A new connection can take the place of
+/// the connection of the session by sending its token, even after the old
+/// connection has dropped, as long as the session is held.
+pub struct Session {
+    token: Token,
+    /// Receives the clients of the new connections that resume the session.
+    resume: Receiver<Client>,
+    /// The time the session is let go, if its connection has dropped.
+    expiry: Option<Instant>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        let token = format!("{:032x}", rand::thread_rng().gen::<u128>());
+        let (sender, resume) = channel(1);
+
+        {
+            SESSIONS
+                .write()
+                .expect("Error acquiring the session index lock")
+                .insert(token.clone(), sender);
+        }
+
+        Self {
+            token,
+            resume,
+            expiry: None,
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Returns true if the connection of the session has dropped.
+    pub fn is_lost(&self) -> bool {
+        self.expiry.is_some()
+    }
+
+    /// Holds the session for the grace period after its connection drops.
+    pub fn lose(&mut self) {
+        self.expiry = Some(Instant::now() + Duration::from_secs(GRACE_SECS));
+    }
+
+    /// Completes with the client of the new connection that resumes the
+    /// session, or `None` if the session is let go before it is resumed.
+    pub async fn resumed(&mut self) -> Option<Client> {
+        let client = match self.expiry {
+            Some(expiry) => timeout_at(expiry, self.resume.recv()).await.ok().flatten(),
+            None => self.resume.recv().await,
+        };
+
+        self.expiry = None;
+        client
+    }
+
+    /// Hands the client over to the session of the token. The client is
+    /// given back if there is no such session, or it is the session of the
+    /// client itself.
+    pub fn resume(token: &str, client: Client) -> Result<(), Client> {
+        if client.token() == Some(token) {
+            return Err(client);
+        }
+
+        let sender = {
+            SESSIONS
+                .read()
+                .expect("Error acquiring the session index lock")
+                .get(token)
+                .cloned()
+        };
+
+        let Some(sender) = sender else {
+            return Err(client);
+        };
+
+        sender.try_send(client).map_err(|error| match error {
+            TrySendError::Full(client) | TrySendError::Closed(client) => client,
+        })
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        SESSIONS
+            .write()
+            .expect("Error acquiring the session index lock")
+            .remove(&self.token);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unregisters_when_dropped() {
+        let session = Session::new();
+        let token = session.token().to_owned();
+
+        assert!(SESSIONS.read().unwrap().contains_key(&token));
+
+        drop(session);
+        assert!(!SESSIONS.read().unwrap().contains_key(&token));
+    }
+}
@@ -0,0 +1,467 @@
+use crate::{scoring::Variant, secret::Format};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt::{Display, Formatter, Result as FormatResult};
+
+/// The rules of the games played in a lobby, chosen by the host.
+///
+/// Like a `Format`, settings are validated when they are deserialized, and
+/// the missing fields are filled with the defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedSettings")]
+pub struct Settings {
+    pub format: Format,
+    pub variant: Variant,
+    pub mode: Mode,
+    /// Warns the players about the guesses contradicting their earlier
+    /// feedback, before the guesses use up the turn.
+    pub assist: bool,
+    /// The duration of a turn in seconds.
+    pub turn_secs: u64,
+    /// The number of guesses a player can make in a game, if it is limited.
+    pub max_guesses: Option<u32>,
+    /// What happens when a player doesn't guess in their turn.
+    pub on_timeout: TimeoutRule,
+    /// The number of turns in a row a player can miss before forfeiting the
+    /// game, if it is limited.
+    pub max_missed_turns: Option<u32>,
+    /// If set, each player has a time budget for the whole game instead of a
+    /// fixed duration for each turn.
+    pub clock: Option<Clock>,
+    /// The number of games in a match series. The series is won by the first
+    /// player who wins the majority of the games.
+    pub best_of: u32,
+    /// The number of members a lobby can hold. When more than two members
+    /// start a game, they play a free-for-all.
+    pub players: u32,
+    /// Whether the members play in two teams. Each team has a secret, and the
+    /// teammates take turns to guess the secret of the other team.
+    pub teams: bool,
+    /// The delay of the events shown to the spectators of the lobby, so that
+    /// they can't help the players.
+    pub spectator_delay_secs: u64,
+}
+
+/// A partial change of the settings of a lobby, sent by the host.
+///
+/// The missing fields keep their current values. The nested objects, like
+/// the format, are merged field by field, and `null` clears an optional
+/// field.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct SettingsUpdate(Map<String, Value>);
+
+/// A chess clock time control. The clock of a player runs only during their
+/// turn, and the player loses the game when their clock runs out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Clock {
+    /// The time budget of each player in seconds.
+    pub budget_secs: u64,
+    /// The time added to the clock of a player after each of their moves.
+    #[serde(default)]
+    pub increment_secs: u64,
+}
+
+/// The way the players take turns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mode {
+    /// The players guess in turns, one after the other.
+    #[default]
+    Alternating,
+    /// Both players guess in each round, and the scores are revealed at the
+    /// same time when the round ends. Hints are not available in this mode.
+    Simultaneous,
+}
+
+/// What happens when the time of a turn is up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeoutRule {
+    /// The turn passes to the opponent.
+    #[default]
+    Pass,
+    /// The player loses the game.
+    Forfeit,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            format: Format::default(),
+            variant: Variant::default(),
+            mode: Mode::default(),
+            assist: false,
+            turn_secs: 20,
+            max_guesses: None,
+            on_timeout: TimeoutRule::default(),
+            max_missed_turns: None,
+            clock: None,
+            best_of: 1,
+            players: 2,
+            teams: false,
+            spectator_delay_secs: 0,
+        }
+    }
+}
+
+impl Settings {
+    pub const MIN_TURN_SECS: u64 = 5;
+    pub const MAX_TURN_SECS: u64 = 300;
+    pub const MIN_BUDGET_SECS: u64 = 10;
+    pub const MAX_BUDGET_SECS: u64 = 3600;
+    pub const MAX_INCREMENT_SECS: u64 = 60;
+    pub const MAX_BEST_OF: u32 = 9;
+    pub const MAX_PLAYERS: u32 = 8;
+    pub const MAX_SPECTATOR_DELAY_SECS: u64 = 600;
+    pub const MAX_MASTERMIND_COLORS: u8 = 8;
+
+    pub fn validate(self) -> Result<Self, SettingsError> {
+        if !(Self::MIN_TURN_SECS..=Self::MAX_TURN_SECS).contains(&self.turn_secs) {
+            return Err(SettingsError::TurnSecs {
+                found: self.turn_secs,
+            });
+        }
+
+        // Mastermind is played with a palette of a few colors, which can be
+        // repeated in a secret.
+        if self.variant == Variant::Mastermind
+            && (!self.format.repeats() || self.format.colors() > Self::MAX_MASTERMIND_COLORS)
+        {
+            return Err(SettingsError::Mastermind);
+        }
+
+        if self.max_guesses == Some(0) {
+            return Err(SettingsError::MaxGuesses);
+        }
+
+        if self.max_missed_turns == Some(0) {
+            return Err(SettingsError::MaxMissedTurns);
+        }
+
+        // An odd number of games can't end with a tie of wins, unless there
+        // are draws.
+        if self.best_of.is_multiple_of(2) || self.best_of > Self::MAX_BEST_OF {
+            return Err(SettingsError::BestOf {
+                found: self.best_of,
+            });
+        }
+
+        if !(2..=Self::MAX_PLAYERS).contains(&self.players) {
+            return Err(SettingsError::Players {
+                found: self.players,
+            });
+        }
+
+        // The players of a free-for-all take turns one after the other, and
+        // they play a single game. The guesses aren't limited or checked
+        // either, and no hints are given.
+        if self.players > 2
+            && (self.mode != Mode::Alternating
+                || self.clock.is_some()
+                || self.best_of > 1
+                || self.max_guesses.is_some()
+                || self.assist)
+        {
+            return Err(SettingsError::FreeForAll);
+        }
+
+        if self.teams && (self.players < 4 || !self.players.is_multiple_of(2)) {
+            return Err(SettingsError::Teams);
+        }
+
+        if self.spectator_delay_secs > Self::MAX_SPECTATOR_DELAY_SECS {
+            return Err(SettingsError::SpectatorDelaySecs {
+                found: self.spectator_delay_secs,
+            });
+        }
+
+        if let Some(clock) = self.clock {
+            if self.mode != Mode::Alternating {
+                return Err(SettingsError::ClockMode);
+            }
+
+            if !(Self::MIN_BUDGET_SECS..=Self::MAX_BUDGET_SECS).contains(&clock.budget_secs) {
+                return Err(SettingsError::BudgetSecs {
+                    found: clock.budget_secs,
+                });
+            }
+
+            if clock.increment_secs > Self::MAX_INCREMENT_SECS {
+                return Err(SettingsError::IncrementSecs {
+                    found: clock.increment_secs,
+                });
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Applies the changes of the update, and validates the result like the
+    /// settings of a new lobby.
+    pub fn update(&self, update: &SettingsUpdate) -> Result<Self, serde_json::Error> {
+        let mut settings = serde_json::to_value(self)?;
+        merge(&mut settings, &update.0);
+        serde_json::from_value(settings)
+    }
+}
+
+fn merge(target: &mut Value, update: &Map<String, Value>) {
+    let Value::Object(target) = target else {
+        *target = Value::Object(update.clone());
+        return;
+    };
+
+    for (key, value) in update {
+        match (target.get_mut(key), value) {
+            (Some(current @ Value::Object(_)), Value::Object(fields)) => merge(current, fields),
+            _ => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// The reason of settings being rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingsError {
+    TurnSecs { found: u64 },
+    Mastermind,
+    MaxGuesses,
+    MaxMissedTurns,
+    BudgetSecs { found: u64 },
+    IncrementSecs { found: u64 },
+    ClockMode,
+    BestOf { found: u32 },
+    Players { found: u32 },
+    FreeForAll,
+    Teams,
+    SpectatorDelaySecs { found: u64 },
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match self {
+            Self::TurnSecs { found } => write!(
+                formatter,
+                "a turn must last {} to {} seconds, not {}",
+                Settings::MIN_TURN_SECS,
+                Settings::MAX_TURN_SECS,
+                found
+            ),
+            Self::Mastermind => write!(
+                formatter,
+                "Mastermind needs repeats and a palette of at most {} colors",
+                Settings::MAX_MASTERMIND_COLORS
+            ),
+            Self::MaxGuesses => formatter.write_str("the guess limit must be at least 1"),
+            Self::MaxMissedTurns => formatter.write_str("the missed turn limit must be at least 1"),
+            Self::BudgetSecs { found } => write!(
+                formatter,
+                "a clock must have {} to {} seconds, not {}",
+                Settings::MIN_BUDGET_SECS,
+                Settings::MAX_BUDGET_SECS,
+                found
+            ),
+            Self::IncrementSecs { found } => write!(
+                formatter,
+                "a clock increment can be at most {} seconds, not {}",
+                Settings::MAX_INCREMENT_SECS,
+                found
+            ),
+            Self::ClockMode => formatter.write_str("a clock can be used only in alternating mode"),
+            Self::BestOf { found } => write!(
+                formatter,
+                "a series must be an odd number of games up to {}, not {}",
+                Settings::MAX_BEST_OF,
+                found
+            ),
+            Self::Players { found } => write!(
+                formatter,
+                "a lobby must hold 2 to {} players, not {}",
+                Settings::MAX_PLAYERS,
+                found
+            ),
+            Self::FreeForAll => formatter.write_str(
+                "a lobby for more than two players can't have a clock, a series, simultaneous turns, \
+                 a guess limit or assist mode",
+            ),
+            Self::Teams => formatter.write_str("a team game needs an even number of at least 4 players"),
+            Self::SpectatorDelaySecs { found } => write!(
+                formatter,
+                "a spectator delay can be at most {} seconds, not {}",
+                Settings::MAX_SPECTATOR_DELAY_SECS,
+                found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+/// The deserialized form of `Settings` before they are validated.
+#[derive(Deserialize)]
+#[serde(default)]
+struct UncheckedSettings {
+    format: Format,
+    variant: Variant,
+    mode: Mode,
+    assist: bool,
+    turn_secs: u64,
+    max_guesses: Option<u32>,
+    on_timeout: TimeoutRule,
+    max_missed_turns: Option<u32>,
+    clock: Option<Clock>,
+    best_of: u32,
+    players: u32,
+    teams: bool,
+    spectator_delay_secs: u64,
+}
+
+impl Default for UncheckedSettings {
+    fn default() -> Self {
+        let Settings {
+            format,
+            variant,
+            mode,
+            assist,
+            turn_secs,
+            max_guesses,
+            on_timeout,
+            max_missed_turns,
+            clock,
+            best_of,
+            players,
+            teams,
+            spectator_delay_secs,
+        } = Settings::default();
+
+        Self {
+            format,
+            variant,
+            mode,
+            assist,
+            turn_secs,
+            max_guesses,
+            on_timeout,
+            max_missed_turns,
+            clock,
+            best_of,
+            players,
+            teams,
+            spectator_delay_secs,
+        }
+    }
+}
+
+impl TryFrom<UncheckedSettings> for Settings {
+    type Error = SettingsError;
+
+    fn try_from(settings: UncheckedSettings) -> Result<Self, Self::Error> {
+        Settings {
+            format: settings.format,
+            variant: settings.variant,
+            mode: settings.mode,
+            assist: settings.assist,
+            turn_secs: settings.turn_secs,
+            max_guesses: settings.max_guesses,
+            on_timeout: settings.on_timeout,
+            max_missed_turns: settings.max_missed_turns,
+            clock: settings.clock,
+            best_of: settings.best_of,
+            players: settings.players,
+            teams: settings.teams,
+            spectator_delay_secs: settings.spectator_delay_secs,
+        }
+        .validate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn fills_missing_fields_with_defaults() {
+        let settings = from_value::<Settings>(json!({ "turn_secs": 60 })).unwrap();
+
+        assert_eq!(
+            settings,
+            Settings {
+                turn_secs: 60,
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn merges_partial_updates() {
+        let settings = from_value::<Settings>(json!({
+            "format": { "length": 4, "repeats": true },
+            "best_of": 3,
+            "clock": { "budget_secs": 180 }
+        }))
+        .unwrap();
+
+        let update = |update| settings.update(&from_value(update).unwrap());
+
+        assert_eq!(
+            update(json!({ "turn_secs": 60 })).unwrap(),
+            Settings {
+                turn_secs: 60,
+                ..settings
+            }
+        );
+        assert_eq!(
+            update(json!({ "format": { "repeats": false } })).unwrap().format,
+            Format::new(4, false).unwrap()
+        );
+        assert_eq!(update(json!({ "clock": null })).unwrap().clock, None);
+        assert!(update(json!({ "players": 4 })).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        assert!(from_value::<Settings>(json!({ "turn_secs": 1 })).is_err());
+        assert!(from_value::<Settings>(json!({ "turn_secs": 1000 })).is_err());
+        assert!(from_value::<Settings>(json!({ "max_guesses": 0 })).is_err());
+        assert!(from_value::<Settings>(json!({ "max_missed_turns": 0 })).is_err());
+        assert!(from_value::<Settings>(json!({ "format": { "length": 12 } })).is_err());
+        assert!(from_value::<Settings>(json!({ "on_timeout": "Forfeit" })).is_ok());
+        assert!(from_value::<Settings>(json!({ "clock": { "budget_secs": 5 } })).is_err());
+        assert!(from_value::<Settings>(json!({
+            "clock": { "budget_secs": 180, "increment_secs": 120 }
+        }))
+        .is_err());
+        assert!(from_value::<Settings>(json!({
+            "clock": { "budget_secs": 180, "increment_secs": 2 }
+        }))
+        .is_ok());
+        assert!(from_value::<Settings>(json!({
+            "mode": "Simultaneous",
+            "clock": { "budget_secs": 180 }
+        }))
+        .is_err());
+        assert!(from_value::<Settings>(json!({ "best_of": 0 })).is_err());
+        assert!(from_value::<Settings>(json!({ "best_of": 4 })).is_err());
+        assert!(from_value::<Settings>(json!({ "best_of": 5 })).is_ok());
+        assert!(from_value::<Settings>(json!({ "players": 1 })).is_err());
+        assert!(from_value::<Settings>(json!({ "players": 4 })).is_ok());
+        assert!(from_value::<Settings>(json!({ "players": 4, "best_of": 3 })).is_err());
+        assert!(from_value::<Settings>(json!({ "players": 4, "max_guesses": 10 })).is_err());
+        assert!(from_value::<Settings>(json!({ "players": 4, "assist": true })).is_err());
+        assert!(from_value::<Settings>(json!({ "players": 3, "teams": true })).is_err());
+        assert!(from_value::<Settings>(json!({ "players": 4, "teams": true })).is_ok());
+        assert!(from_value::<Settings>(json!({ "spectator_delay_secs": 1200 })).is_err());
+        assert!(from_value::<Settings>(json!({ "variant": "Mastermind" })).is_err());
+        assert!(from_value::<Settings>(json!({
+            "variant": "Mastermind",
+            "format": { "length": 4, "repeats": true }
+        }))
+        .is_err());
+        assert!(from_value::<Settings>(json!({
+            "variant": "Mastermind",
+            "format": { "length": 4, "repeats": true, "colors": 6 }
+        }))
+        .is_ok());
+    }
+}
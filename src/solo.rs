@@ -0,0 +1,142 @@
+use crate::{
+    client::{Client, ListenError, Listener, ListenerState},
+    game::Record,
+    message::ErrorCode,
+    Directive, Idler, Notification, Secret, Settings,
+};
+use log::debug;
+use tokio::time::Instant;
+
+/// A single player game against a secret chosen by the server. There are no
+/// turns, the player guesses until the secret is found.
+pub struct Solo {
+    state: ListenerState,
+    secret: Secret,
+    settings: Settings,
+    /// The guesses of the player so far, with their feedback.
+    history: Vec<Record>,
+    start: Instant,
+}
+
+impl Listener for Solo {
+    fn state(&self) -> &ListenerState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut ListenerState {
+        &mut self.state
+    }
+}
+
+impl Solo {
+    pub fn spawn(client: Client, settings: Settings) {
+        let solo = Self {
+            state: ListenerState::Listen(client),
+            secret: Secret::random(&settings.format, &mut rand::thread_rng()),
+            settings,
+            history: Vec::new(),
+            start: Instant::now(),
+        };
+
+        tokio::spawn(solo.listen());
+    }
+
+    async fn listen(mut self) {
+        debug!("Listening to a solo player");
+
+        let settings = self.settings;
+
+        let _ = self
+            .client_mut()
+            .unwrap()
+            .notify(Notification::SoloStart { settings })
+            .await;
+
+        while let Some(mut solo) = self.bundle() {
+            use Directive::*;
+
+            match solo.client.listen().await {
+                Ok(directive) => match directive {
+                    Guess { secret, .. } => {
+                        let secret = match secret.parse(&solo.listener.settings.format) {
+                            Ok(secret) => secret,
+                            Err(error) => {
+                                solo.client.reject_secret(error, "Guess").await;
+
+                                solo.reunite();
+                                continue;
+                            }
+                        };
+
+                        let feedback = solo
+                            .listener
+                            .settings
+                            .variant
+                            .rule()
+                            .score(&solo.listener.secret, &secret);
+
+                        let solved = solo.listener.secret == secret;
+                        let elapsed_ms = solo.listener.start.elapsed().as_millis() as u64;
+
+                        solo.listener.history.push(Record {
+                            guess: secret,
+                            feedback,
+                            elapsed_ms,
+                        });
+
+                        if solved {
+                            let _ = solo
+                                .client
+                                .notify(Notification::SoloWin {
+                                    guesses: solo.listener.history.len() as u32,
+                                    elapsed_ms,
+                                })
+                                .await;
+
+                            Idler::spawn(solo.client);
+                        } else {
+                            let record = solo.listener.history.last().unwrap();
+
+                            let _ = solo
+                                .client
+                                .notify(Notification::GuessScore {
+                                    secret: &record.guess,
+                                    feedback: record.feedback.clone(),
+                                })
+                                .await;
+
+                            solo.reunite();
+                        }
+                    }
+                    GetState => {
+                        let _ = solo
+                            .client
+                            .notify(Notification::SoloState {
+                                settings: solo.listener.settings,
+                                guesses: solo.listener.history.len() as u32,
+                                history: &solo.listener.history,
+                            })
+                            .await;
+
+                        solo.reunite();
+                    }
+                    Leave => Idler::spawn(solo.client),
+
+                    // The state remains `Stop` so the client gets dropped.
+                    CloseConnection => {}
+                    directive => {
+                        solo.client.reject(ErrorCode::UnexpectedDirective, directive.name()).await;
+                        solo.reunite();
+                    }
+                },
+                Err(ListenError::SocketExhausted) => {}
+                Err(error) => {
+                    solo.client.report(&error).await;
+                    solo.reunite();
+                }
+            }
+        }
+
+        debug!("Dropping a solo listener");
+    }
+}
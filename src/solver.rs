@@ -0,0 +1,301 @@
+use crate::{
+    scoring::{Feedback, ScoringRule, Variant},
+    secret::Format,
+    Secret,
+};
+use std::collections::HashMap;
+
+/// The way a solver chooses the next guess among the options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Minimizes the number of candidates left in the worst case.
+    Minimax,
+    /// Maximizes the expected information gained from the feedback.
+    Entropy,
+}
+
+/// Keeps track of the secrets that are consistent with the feedback of the
+/// guesses so far.
+///
+/// The feedback is computed with the scoring rules, which are built on
+/// `Secret::score`, so a solver always agrees with the server.
+pub struct Solver {
+    rule: &'static dyn ScoringRule,
+    candidates: Vec<Secret>,
+}
+
+impl Solver {
+    pub fn new(format: &Format, variant: Variant) -> Self {
+        Self {
+            rule: variant.rule(),
+            candidates: all_secrets(format),
+        }
+    }
+
+    /// Creates a solver from the feedback of the earlier guesses.
+    pub fn from_history<'a, I>(format: &Format, variant: Variant, history: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a Secret, &'a Feedback)>,
+    {
+        let mut solver = Self::new(format, variant);
+
+        for (guess, feedback) in history {
+            solver.record(guess, feedback);
+        }
+
+        solver
+    }
+
+    /// The secrets that are consistent with the feedback so far.
+    pub fn candidates(&self) -> &[Secret] {
+        &self.candidates
+    }
+
+    /// Discards the candidates that are inconsistent with the feedback of a
+    /// guess.
+    pub fn record(&mut self, guess: &Secret, feedback: &Feedback) {
+        let rule = self.rule;
+
+        self.candidates
+            .retain(|candidate| rule.score(candidate, guess) == *feedback);
+    }
+
+    /// Chooses the best of the options to tell the candidates apart with the
+    /// strategy, ties are broken in favor of the earlier options.
+    pub fn best<'a, I>(&self, candidates: &[Secret], options: I, strategy: Strategy) -> Option<&'a Secret>
+    where
+        I: IntoIterator<Item = &'a Secret>,
+    {
+        let mut best = None;
+
+        for option in options {
+            let cost = self.cost(candidates, option, strategy);
+
+            if best.as_ref().is_none_or(|&(_, best)| cost < best) {
+                best = Some((option, cost));
+            }
+        }
+
+        best.map(|(option, _)| option)
+    }
+
+    /// Suggests the next guess among the candidates.
+    pub fn suggest(&self, strategy: Strategy) -> Option<&Secret> {
+        self.best(&self.candidates, &self.candidates, strategy)
+    }
+
+    /// Returns the number of guesses needed to find any of the candidates in
+    /// the worst case, when the guesses are suggested with the strategy.
+    pub fn worst_case(&self, strategy: Strategy) -> usize {
+        self.depth(&self.candidates, strategy)
+    }
+
+    fn depth(&self, candidates: &[Secret], strategy: Strategy) -> usize {
+        let guess = match self.best(candidates, candidates, strategy) {
+            Some(guess) => guess,
+            None => return 0,
+        };
+
+        // The guess itself is found with this guess. The rest of the
+        // candidates are told apart by the feedback.
+        self.partition(candidates, guess)
+            .into_values()
+            .map(|partition| {
+                let rest: Vec<_> = partition
+                    .into_iter()
+                    .filter(|&candidate| candidate != guess)
+                    .cloned()
+                    .collect();
+
+                self.depth(&rest, strategy)
+            })
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    fn partition<'a>(
+        &self,
+        candidates: &'a [Secret],
+        guess: &Secret,
+    ) -> HashMap<Feedback, Vec<&'a Secret>> {
+        let mut partitions: HashMap<_, Vec<_>> = HashMap::new();
+
+        for candidate in candidates {
+            partitions
+                .entry(self.rule.score(candidate, guess))
+                .or_default()
+                .push(candidate);
+        }
+
+        partitions
+    }
+
+    /// The cost of a guess for the strategy, the lower the better.
+    fn cost(&self, candidates: &[Secret], guess: &Secret, strategy: Strategy) -> f64 {
+        let mut sizes: HashMap<Feedback, usize> = HashMap::new();
+
+        for candidate in candidates {
+            *sizes.entry(self.rule.score(candidate, guess)).or_default() += 1;
+        }
+
+        match strategy {
+            Strategy::Minimax => sizes.into_values().max().unwrap_or(0) as f64,
+            Strategy::Entropy => {
+                let total = candidates.len() as f64;
+
+                sizes
+                    .into_values()
+                    .map(|size| {
+                        let p = size as f64 / total;
+                        p * p.log2()
+                    })
+                    .sum()
+            }
+        }
+    }
+}
+
+/// Enumerates every secret that is valid in the format, in ascending order.
+///
+/// Only the valid secrets are generated, the permutations of the colors or,
+/// if the format allows repeats, their product.
+pub fn all_secrets(format: &Format) -> Vec<Secret> {
+    let mut secrets = Vec::with_capacity(count_secrets(format));
+    extend(format, &mut Vec::with_capacity(format.length()), &mut secrets);
+    secrets
+}
+
+/// Completes the digits of a secret in every valid way.
+fn extend(format: &Format, digits: &mut Vec<u8>, secrets: &mut Vec<Secret>) {
+    if digits.len() == format.length() {
+        let secret = Secret::from_digits(digits.clone(), format).expect("Generated an invalid secret");
+        secrets.push(secret);
+        return;
+    }
+
+    for digit in 0..format.colors() {
+        if format.repeats() || !digits.contains(&digit) {
+            digits.push(digit);
+            extend(format, digits, secrets);
+            digits.pop();
+        }
+    }
+}
+
+/// Returns the number of the secrets that are valid in the format.
+pub fn count_secrets(format: &Format) -> usize {
+    let length = format.length();
+    let colors = format.colors() as usize;
+
+    if format.repeats() {
+        colors.pow(length as u32)
+    } else {
+        (colors - length + 1..=colors).product()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secret(text: &str) -> Secret {
+        Secret::parse(text).unwrap()
+    }
+
+    #[test]
+    fn enumerates_all_secrets() {
+        for length in 3..=5 {
+            for repeats in [false, true] {
+                let format = Format::new(length, repeats).unwrap();
+                assert_eq!(all_secrets(&format).len(), count_secrets(&format));
+            }
+        }
+
+        let format = Format::with_colors(4, true, 6).unwrap();
+        assert_eq!(all_secrets(&format).len(), 6usize.pow(4));
+
+        // The same secrets as the valid numbers of the format.
+        for repeats in [false, true] {
+            let format = Format::new(4, repeats).unwrap();
+            let numbers: Vec<_> = (0..10_000)
+                .filter_map(|n| Secret::from_number(n, &format).ok())
+                .collect();
+
+            assert_eq!(all_secrets(&format), numbers);
+        }
+    }
+
+    #[test]
+    fn filters_candidates() {
+        let guess = secret("123");
+        let feedback = Feedback::BullsAndCows { correct: 2, wrong: 0 };
+        let solver = Solver::from_history(&Format::default(), Variant::Classic, [(&guess, &feedback)]);
+
+        // Two of the digits are kept, the other one is any of the remaining
+        // seven digits.
+        assert_eq!(solver.candidates().len(), 3 * 7);
+        assert!(solver
+            .candidates()
+            .iter()
+            .all(|candidate| candidate.score(&guess) == (2, 0)));
+    }
+
+    #[test]
+    fn suggests_consistent_guesses() {
+        let target = secret("907");
+        let mut solver = Solver::new(&Format::default(), Variant::Classic);
+
+        for _ in 0..10 {
+            let guess = solver.suggest(Strategy::Minimax).unwrap().clone();
+
+            if guess == target {
+                return;
+            }
+
+            solver.record(&guess, &Variant::Classic.rule().score(&target, &guess));
+            assert!(solver.candidates().contains(&target));
+        }
+
+        panic!("The solver couldn't find the secret");
+    }
+
+    #[test]
+    fn finds_worst_cases() {
+        let format = Format::default();
+        let rule = Variant::Classic.rule();
+        let solver = Solver::new(&format, Variant::Classic);
+
+        // The games that have had the same feedback so far are given the same
+        // guess, so the guesses are only chosen once.
+        let mut suggestions: HashMap<Vec<Feedback>, Secret> = HashMap::new();
+
+        let played = solver
+            .candidates()
+            .iter()
+            .map(|target| {
+                let mut solver = Solver::new(&format, Variant::Classic);
+                let mut history = Vec::new();
+
+                for guesses in 1.. {
+                    let guess = suggestions
+                        .entry(history.clone())
+                        .or_insert_with(|| solver.suggest(Strategy::Minimax).unwrap().clone())
+                        .clone();
+
+                    if guess == *target {
+                        return guesses;
+                    }
+
+                    let feedback = rule.score(target, &guess);
+                    solver.record(&guess, &feedback);
+                    history.push(feedback);
+                }
+
+                unreachable!()
+            })
+            .max();
+
+        assert_eq!(played, Some(solver.worst_case(Strategy::Minimax)));
+    }
+}
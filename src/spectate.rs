@@ -0,0 +1,162 @@
+use crate::{
+    client::{Client, ListenError, Listener, ListenerState},
+    game::Ending,
+    message::ErrorCode,
+    scoring::Feedback,
+    Directive, Idler, Notification, Secret,
+};
+use log::debug;
+use serde::Serialize;
+use std::collections::VecDeque;
+use tokio::{
+    select,
+    sync::broadcast::{self, error::RecvError},
+    time::{sleep_until, Duration, Instant},
+};
+
+/// The number of events a spectator can fall behind before missing some.
+pub const FEED_CAPACITY: usize = 64;
+
+/// What the spectators of a lobby see of its games. The players are
+/// identified by their seats, and the secrets are never revealed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event")]
+pub enum Event {
+    /// The nicknames of the players are in the order of their seats.
+    GameStart { nicknames: Vec<Option<String>> },
+    /// The turn of a player starts. In simultaneous mode, the seat is `None`
+    /// since every player moves in a round.
+    NextTurn { seat: Option<usize> },
+    Guess {
+        seat: usize,
+        /// The seat of the player whose secret is guessed, in a free-for-all.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target: Option<usize>,
+        guess: Secret,
+        #[serde(flatten)]
+        feedback: Feedback,
+    },
+    Eliminated { seat: usize, by: Option<usize> },
+    /// The winner is a seat, or a team in team mode.
+    GameEnd {
+        winner: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ending: Option<Ending>,
+    },
+}
+
+/// An event, and the time it can be shown to the spectators.
+#[derive(Debug, Clone)]
+pub struct Release {
+    at: Instant,
+    event: Event,
+}
+
+/// Publishes the events of a game to the spectators of its lobby, who see
+/// them after the delay.
+#[derive(Clone)]
+pub struct Feed {
+    sender: broadcast::Sender<Release>,
+    delay: Duration,
+}
+
+impl Feed {
+    pub fn new(sender: broadcast::Sender<Release>, delay_secs: u64) -> Self {
+        Self {
+            sender,
+            delay: Duration::from_secs(delay_secs),
+        }
+    }
+
+    pub fn publish(&self, event: Event) {
+        // Having no spectators is not an error.
+        let _ = self.sender.send(Release {
+            at: Instant::now() + self.delay,
+            event,
+        });
+    }
+}
+
+pub struct Spectator {
+    state: ListenerState,
+    feed: broadcast::Receiver<Release>,
+    /// The events that are received, but not shown yet.
+    pending: VecDeque<Release>,
+    /// Whether the lobby is gone. The pending events are still shown.
+    closed: bool,
+}
+
+impl Listener for Spectator {
+    fn state(&self) -> &ListenerState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut ListenerState {
+        &mut self.state
+    }
+}
+
+impl Spectator {
+    pub fn spawn(client: Client, feed: broadcast::Receiver<Release>) {
+        let spectator = Self {
+            state: ListenerState::Listen(client),
+            feed,
+            pending: VecDeque::new(),
+            closed: false,
+        };
+
+        tokio::spawn(spectator.listen());
+    }
+
+    async fn listen(mut self) {
+        debug!("Listening to a spectator");
+
+        while let Some(mut client) = self.take() {
+            // There is nothing left to watch.
+            if self.closed && self.pending.is_empty() {
+                let _ = client.notify(Notification::SpectateEnd).await;
+                Idler::spawn(client);
+
+                break;
+            }
+
+            let next_release = self.pending.front().map(|release| release.at);
+
+            select! {
+                result = client.listen() => match result {
+                    Ok(Directive::Leave) => Idler::spawn(client),
+                    // The state remains `Stop` so the client gets dropped.
+                    Ok(Directive::CloseConnection) | Err(ListenError::SocketExhausted) => {}
+                    // The events are the state of a spectator.
+                    Ok(Directive::GetState) => self.attach(client),
+                    Ok(directive) => {
+                        client.reject(ErrorCode::UnexpectedDirective, directive.name()).await;
+                        self.attach(client);
+                    }
+                    Err(error) => {
+                        client.report(&error).await;
+                        self.attach(client);
+                    }
+                },
+                received = self.feed.recv(), if !self.closed => {
+                    match received {
+                        Ok(release) => self.pending.push_back(release),
+                        // Some events are missed, but the rest can be shown.
+                        Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => self.closed = true,
+                    }
+
+                    self.attach(client);
+                },
+                _ = sleep_until(next_release.unwrap_or_else(Instant::now)), if next_release.is_some() => {
+                    let release = self.pending.pop_front().unwrap();
+                    let _ = client.notify(Notification::Spectate { event: release.event }).await;
+
+                    self.attach(client);
+                },
+            }
+        }
+
+        debug!("Dropping a spectator listener");
+    }
+}
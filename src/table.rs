@@ -0,0 +1,275 @@
+use crate::{
+    client::{Client, ListenError, ListenResult, Listener},
+    game::{Ending, Return},
+    message::ErrorCode,
+    settings::TimeoutRule,
+    spectate::{Event, Feed},
+    Directive, Idler, Notification, Secret, Settings,
+};
+use futures_util::{
+    future::{join_all, select_all},
+    FutureExt,
+};
+use log::debug;
+use std::future::Future;
+use tokio::{
+    select,
+    sync::oneshot,
+    time::{sleep_until, Duration, Instant},
+};
+
+/// The players of a game where they take turns one at a time, like a
+/// free-for-all or a team game.
+///
+/// The table listens to the players, keeps the time of the turns, and sends
+/// the players back to the lobby when the game ends. What the moves of the
+/// players do is up to the `Rules` of the game.
+pub struct Table<S> {
+    pub seats: Vec<S>,
+    pub settings: Settings,
+    /// The seat of the player whose turn it is.
+    pub turn: usize,
+    pub deadline: Instant,
+    pub start: Instant,
+    pub feed: Feed,
+    lobby: oneshot::Sender<Return>,
+}
+
+/// A player sitting at a table.
+pub trait Seat: Listener + Send {
+    /// The number of turns in a row the player has missed.
+    fn missed_turns(&mut self) -> &mut u32;
+}
+
+/// The rules of a game played at a table.
+pub trait Rules: Send {
+    type Seat: Seat;
+
+    /// Returns true if the game is over.
+    fn is_over(&self, table: &Table<Self::Seat>) -> bool;
+
+    /// Returns true if the player in the seat can guess now.
+    fn can_guess(&self, table: &Table<Self::Seat>, seat: usize) -> bool {
+        seat == table.turn
+    }
+
+    /// Chooses the seat of the next turn, or returns `None` if the game ends
+    /// instead.
+    fn next_turn(&mut self, table: &Table<Self::Seat>) -> Option<usize>;
+
+    /// Notifies the players that the game starts. The nicknames are in the
+    /// order of the seats.
+    fn start(
+        &mut self,
+        table: &mut Table<Self::Seat>,
+        nicknames: &[Option<String>],
+    ) -> impl Future<Output = ()> + Send;
+
+    /// Scores a valid guess of the player in turn.
+    fn guess(
+        &mut self,
+        table: &mut Table<Self::Seat>,
+        seat: usize,
+        guess: Secret,
+        target: Option<usize>,
+    ) -> impl Future<Output = ()> + Send;
+
+    /// Makes the player lose, because they have resigned or they have run out
+    /// of time.
+    fn forfeit(
+        &mut self,
+        table: &mut Table<Self::Seat>,
+        seat: usize,
+        ending: Ending,
+    ) -> impl Future<Output = ()> + Send;
+
+    /// Called after the client of the player is taken from the table.
+    fn leave(&mut self, table: &mut Table<Self::Seat>, seat: usize) -> impl Future<Output = ()> + Send;
+
+    /// Handles the directives that only the game knows about, and rejects
+    /// the rest.
+    fn handle(
+        &mut self,
+        table: &mut Table<Self::Seat>,
+        seat: usize,
+        directive: Directive,
+    ) -> impl Future<Output = ()> + Send;
+
+    /// Notifies the players that the game ends, and reveals the secrets.
+    fn end(&mut self, table: &mut Table<Self::Seat>) -> impl Future<Output = ()> + Send;
+}
+
+impl<S: Seat> Table<S> {
+    pub fn new(seats: Vec<S>, settings: Settings, lobby: oneshot::Sender<Return>, feed: Feed) -> Self {
+        let now = Instant::now();
+
+        Self {
+            seats,
+            settings,
+            turn: 0,
+            deadline: now + Duration::from_secs(settings.turn_secs),
+            start: now,
+            feed,
+            lobby,
+        }
+    }
+
+    pub async fn run<R: Rules<Seat = S>>(mut self, mut rules: R) {
+        debug!("Listening to player directives at a table");
+
+        let nicknames: Vec<_> = self
+            .seats
+            .iter()
+            .map(|seat| seat.client().and_then(Client::nickname).map(str::to_owned))
+            .collect();
+
+        rules.start(&mut self, &nicknames).await;
+
+        self.feed.publish(Event::GameStart { nicknames });
+        self.notify_turn().await;
+
+        while !rules.is_over(&self) {
+            // Each listen future is paired with the seat of its player.
+            let listen_futures: Vec<_> = self
+                .seats
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(seat, player)| {
+                    let client = player.client_mut()?;
+                    Some(Box::pin(async move { (seat, client.listen().await) }))
+                })
+                .collect();
+
+            if listen_futures.is_empty() {
+                break;
+            }
+
+            select! {
+                _ = sleep_until(self.deadline) => self.on_timeout(&mut rules).await,
+                (seat, result) = select_all(listen_futures).map(|(output, ..)| output) => {
+                    self.handle(&mut rules, seat, result).await
+                }
+            }
+        }
+
+        rules.end(&mut self).await;
+
+        // The players who are still connected go back to the lobby.
+        let back = Return {
+            clients: self.seats.iter_mut().map(Listener::take).collect(),
+            series: None,
+        };
+
+        if let Err(back) = self.lobby.send(back) {
+            back.clients.into_iter().flatten().for_each(Idler::spawn);
+        }
+
+        debug!("Dropping a table listener");
+    }
+
+    /// Notifies every player who is still connected.
+    pub async fn notify_all<'a, F>(&mut self, notification: F)
+    where
+        F: Fn() -> Notification<'a>,
+    {
+        join_all(
+            self.seats
+                .iter_mut()
+                .filter_map(|seat| seat.client_mut())
+                .map(|client| client.notify(notification())),
+        )
+        .await;
+    }
+
+    /// Notifies the player in the seat that the directive is rejected.
+    pub async fn reject(&mut self, seat: usize, code: ErrorCode, directive: &'static str) {
+        if let Some(client) = self.seats[seat].client_mut() {
+            client.reject(code, directive).await;
+        }
+    }
+
+    /// Passes the turn to the seat chosen by the rules, unless the game ends.
+    pub async fn pass_turn<R: Rules<Seat = S>>(&mut self, rules: &mut R) {
+        if let Some(seat) = rules.next_turn(self) {
+            self.turn = seat;
+            self.deadline = Instant::now() + Duration::from_secs(self.settings.turn_secs);
+
+            self.notify_turn().await;
+        }
+    }
+
+    async fn notify_turn(&mut self) {
+        self.feed.publish(Event::NextTurn { seat: Some(self.turn) });
+
+        let player = &mut self.seats[self.turn];
+        let missed_turns = *player.missed_turns();
+
+        if let Some(client) = player.client_mut() {
+            let _ = client
+                .notify(Notification::NextTurn {
+                    clocks: None,
+                    missed_turns,
+                })
+                .await;
+        }
+    }
+
+    async fn on_timeout<R: Rules<Seat = S>>(&mut self, rules: &mut R) {
+        let seat = self.turn;
+        let missed_turns = self.seats[seat].missed_turns();
+        *missed_turns += 1;
+
+        let idle = self
+            .settings
+            .max_missed_turns
+            .is_some_and(|max| *missed_turns >= max);
+
+        if self.settings.on_timeout == TimeoutRule::Forfeit {
+            rules.forfeit(self, seat, Ending::Timeout).await;
+        } else if idle {
+            rules.forfeit(self, seat, Ending::Idle).await;
+        } else {
+            self.pass_turn(rules).await;
+        }
+    }
+
+    async fn handle<R: Rules<Seat = S>>(&mut self, rules: &mut R, seat: usize, result: ListenResult) {
+        use Directive::*;
+
+        match result {
+            Ok(directive) => match directive {
+                Guess { secret, target } if rules.can_guess(self, seat) => {
+                    match secret.parse(&self.settings.format) {
+                        Ok(guess) => {
+                            *self.seats[seat].missed_turns() = 0;
+                            rules.guess(self, seat, guess, target).await;
+                        }
+                        Err(error) => {
+                            // A rejected guess doesn't use up the turn.
+                            let client = self.seats[seat].client_mut().unwrap();
+                            client.reject_secret(error, "Guess").await;
+                        }
+                    }
+                }
+                Guess { .. } => self.reject(seat, ErrorCode::NotYourTurn, "Guess").await,
+                Resign => rules.forfeit(self, seat, Ending::Resign).await,
+                Leave => {
+                    let client = self.seats[seat].take().unwrap();
+                    Idler::spawn(client);
+
+                    rules.leave(self, seat).await;
+                }
+                CloseConnection => {
+                    self.seats[seat].take();
+                    rules.leave(self, seat).await;
+                }
+                directive => rules.handle(self, seat, directive).await,
+            },
+            Err(ListenError::SocketExhausted) => {
+                self.seats[seat].take();
+                rules.leave(self, seat).await;
+            }
+            Err(error) => self.seats[seat].client_mut().unwrap().report(&error).await,
+        }
+    }
+}
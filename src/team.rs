@@ -0,0 +1,257 @@
+use crate::{
+    client::{Client, Listener, ListenerState},
+    game::{Ending, Return},
+    message::{ErrorCode, MAX_CHAT_LENGTH},
+    spectate::{Event, Feed},
+    table::{Rules, Seat, Table},
+    Directive, Notification, Secret, Settings,
+};
+use futures_util::future::join_all;
+use tokio::sync::oneshot;
+
+/// A game of two teams, each with a secret. The teams take turns, and in the
+/// turns of a team, its members take turns to guess the secret of the other
+/// team. The teammates can talk in a chat of their own.
+pub struct TeamGame {
+    secrets: [Secret; 2],
+    /// The seat of the player who has played last in each team.
+    last: [usize; 2],
+    /// The winner of the game, if there is one, and the way it has ended.
+    result: Option<(Option<usize>, Ending)>,
+}
+
+pub struct Teammate {
+    state: ListenerState,
+    team: usize,
+    /// The number of turns in a row the player has missed.
+    missed_turns: u32,
+}
+
+impl Listener for Teammate {
+    fn state(&self) -> &ListenerState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut ListenerState {
+        &mut self.state
+    }
+}
+
+impl Seat for Teammate {
+    fn missed_turns(&mut self) -> &mut u32 {
+        &mut self.missed_turns
+    }
+}
+
+impl TeamGame {
+    pub fn spawn(
+        players: Vec<(Client, usize)>,
+        secrets: [Secret; 2],
+        settings: Settings,
+        lobby: oneshot::Sender<Return>,
+        feed: Feed,
+    ) {
+        let last = players.len() - 1;
+
+        let players = players
+            .into_iter()
+            .map(|(client, team)| Teammate {
+                state: ListenerState::Listen(client),
+                team,
+                missed_turns: 0,
+            })
+            .collect();
+
+        let mut table = Table::new(players, settings, lobby, feed);
+        let mut game = Self {
+            secrets,
+            last: [last; 2],
+            result: None,
+        };
+
+        // The first team moves first.
+        if let Some(seat) = game.next_of(&table, 0) {
+            table.turn = seat;
+            game.last[0] = seat;
+        }
+
+        tokio::spawn(table.run(game));
+    }
+
+    /// The seat of the next player of the team who is still connected, in
+    /// the order of the seats.
+    fn next_of(&self, table: &Table<Teammate>, team: usize) -> Option<usize> {
+        let count = table.seats.len();
+
+        (1..=count)
+            .map(|offset| (self.last[team] + offset) % count)
+            .find(|&seat| table.seats[seat].team == team && table.seats[seat].is_listening())
+    }
+}
+
+/// Notifies the connected players of the team, except the player in the
+/// given seat.
+async fn notify_team<'a, F>(table: &mut Table<Teammate>, team: usize, except: usize, notification: F)
+where
+    F: Fn() -> Notification<'a>,
+{
+    join_all(
+        table
+            .seats
+            .iter_mut()
+            .enumerate()
+            .filter(|(seat, player)| player.team == team && *seat != except)
+            .filter_map(|(_, player)| player.client_mut())
+            .map(|client| client.notify(notification())),
+    )
+    .await;
+}
+
+impl Rules for TeamGame {
+    type Seat = Teammate;
+
+    fn is_over(&self, _: &Table<Teammate>) -> bool {
+        self.result.is_some()
+    }
+
+    /// The next player of the other team. If nobody is left in the other
+    /// team, the team of the current player wins.
+    fn next_turn(&mut self, table: &Table<Teammate>) -> Option<usize> {
+        let team = table.seats[table.turn].team;
+
+        match self.next_of(table, 1 - team) {
+            Some(seat) => {
+                self.last[1 - team] = seat;
+                Some(seat)
+            }
+            None => {
+                self.result = Some((Some(team), Ending::Forfeit));
+                None
+            }
+        }
+    }
+
+    async fn start(&mut self, table: &mut Table<Teammate>, nicknames: &[Option<String>]) {
+        join_all(table.seats.iter_mut().enumerate().map(|(seat, player)| {
+            let team = player.team;
+            player.client_mut().unwrap().notify(Notification::TeamGameStart {
+                seat,
+                team,
+                nicknames: nicknames.iter().map(Option::as_deref).collect(),
+            })
+        }))
+        .await;
+    }
+
+    async fn guess(&mut self, table: &mut Table<Teammate>, seat: usize, guess: Secret, _: Option<usize>) {
+        let team = table.seats[seat].team;
+
+        let secret = &self.secrets[1 - team];
+        let feedback = table.settings.variant.rule().score(secret, &guess);
+        let solved = *secret == guess;
+
+        table.feed.publish(Event::Guess {
+            seat,
+            target: None,
+            guess: guess.clone(),
+            feedback: feedback.clone(),
+        });
+
+        let _ = table.seats[seat]
+            .client_mut()
+            .unwrap()
+            .notify(Notification::GuessScore {
+                secret: &guess,
+                feedback: feedback.clone(),
+            })
+            .await;
+
+        notify_team(table, team, seat, || Notification::TeammateGuess {
+            seat,
+            secret: &guess,
+            feedback: feedback.clone(),
+        })
+        .await;
+
+        if solved {
+            self.result = Some((Some(team), Ending::Solved));
+        } else {
+            table.pass_turn(self).await;
+        }
+    }
+
+    /// The whole team of the player loses.
+    async fn forfeit(&mut self, table: &mut Table<Teammate>, seat: usize, ending: Ending) {
+        let team = table.seats[seat].team;
+        self.result = Some((Some(1 - team), ending));
+    }
+
+    /// Ends the game if the team of the player who has left has nobody left.
+    async fn leave(&mut self, table: &mut Table<Teammate>, seat: usize) {
+        let team = table.seats[seat].team;
+
+        let empty = !table
+            .seats
+            .iter()
+            .any(|player| player.team == team && player.is_listening());
+
+        if empty {
+            self.result = Some((Some(1 - team), Ending::Forfeit));
+        } else if seat == table.turn {
+            table.pass_turn(self).await;
+        }
+    }
+
+    async fn handle(&mut self, table: &mut Table<Teammate>, seat: usize, directive: Directive) {
+        use Directive::*;
+
+        let team = table.seats[seat].team;
+
+        match directive {
+            TeamChat { message } if message.len() > MAX_CHAT_LENGTH => {
+                table.reject(seat, ErrorCode::ChatTooLong, "TeamChat").await;
+            }
+            TeamChat { message } => {
+                notify_team(table, team, seat, || Notification::TeamChat {
+                    seat,
+                    message: &message,
+                })
+                .await;
+            }
+            GetState => {
+                let turn = table.turn;
+                let secret = &self.secrets[team];
+
+                let _ = table.seats[seat]
+                    .client_mut()
+                    .unwrap()
+                    .notify(Notification::TeamGameState {
+                        seat,
+                        team,
+                        turn,
+                        secret,
+                    })
+                    .await;
+            }
+            directive => table.reject(seat, ErrorCode::UnexpectedDirective, directive.name()).await,
+        }
+    }
+
+    /// Reveals the secrets of both teams.
+    async fn end(&mut self, table: &mut Table<Teammate>) {
+        let (winner, ending) = self.result.unwrap_or((None, Ending::Disconnect));
+        table.feed.publish(Event::GameEnd { winner, ending: Some(ending) });
+
+        let duration_ms = table.start.elapsed().as_millis() as u64;
+        let [first, second] = &self.secrets;
+
+        table
+            .notify_all(|| Notification::TeamGameEnd {
+                winner,
+                ending,
+                duration_ms,
+                secrets: [first, second],
+            })
+            .await;
+    }
+}